@@ -1,6 +1,9 @@
-use std::process::Command;
-
+// Shuttle only deploys what the build produces, so the frontend has to be built along with the API.
+// The standalone server serves whatever `--static-dir` points at instead.
+#[cfg(feature = "shuttle")]
 fn main() {
+    use std::process::Command;
+
     let release = std::env::var("PROFILE").unwrap() == "release";
     println!("cargo:rerun-if-changed=../sombra-lookup");
     std::env::set_current_dir("../sombra-lookup").unwrap();
@@ -23,3 +26,8 @@ fn main() {
         }
     }
 }
+
+#[cfg(not(feature = "shuttle"))]
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+}
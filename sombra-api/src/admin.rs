@@ -0,0 +1,176 @@
+use std::{path::PathBuf, sync::Arc};
+
+use poem_openapi::{
+    auth::ApiKey,
+    param::Query,
+    payload::{Json, PlainText},
+    OpenApi, SecurityScheme,
+};
+use sombra::{
+    Battletag, CacheEntryInfo, CachedClient, Canary, DriftReport, PlayerProfile, Selectors,
+    SelectorsReport, WarmReport,
+};
+
+use crate::{
+    battletag,
+    cli::Cli,
+    config::Config,
+    error::{Error, Result},
+    ApiTags,
+};
+
+/// Key configured as `admin.key`
+#[derive(SecurityScheme)]
+#[oai(ty = "api_key", key_name = "X-Admin-Key", key_in = "header")]
+pub struct AdminKey(ApiKey);
+
+pub struct AdminApi {
+    client: Arc<CachedClient>,
+    key: Option<String>,
+    warm_concurrency: usize,
+    /// Selector configuration that is reloaded
+    selectors: Option<PathBuf>,
+    /// Saved pages that selectors are checked against
+    fixtures: PathBuf,
+}
+
+impl AdminApi {
+    pub fn new(client: Arc<CachedClient>, cli: &Cli, config: &Config) -> Self {
+        Self {
+            client,
+            key: config.admin.key.clone(),
+            warm_concurrency: config.limits.warm_concurrency,
+            selectors: cli.selectors.clone(),
+            fixtures: cli.fixtures.clone(),
+        }
+    }
+
+    fn authorize(&self, key: &AdminKey) -> Result<()> {
+        match &self.key {
+            Some(expected) if *expected == key.0.key => Ok(()),
+            _ => Err(Error::unauthorized()),
+        }
+    }
+}
+
+#[OpenApi(prefix_path = "/admin", tag = "ApiTags::Admin")]
+impl AdminApi {
+    #[oai(path = "/cache", method = "get")]
+    async fn entries(&self, key: AdminKey) -> Result<Json<Vec<CacheEntryInfo>>> {
+        self.authorize(&key)?;
+        Ok(Json(self.client.entries()))
+    }
+
+    #[oai(path = "/cache", method = "delete")]
+    async fn clear(&self, key: AdminKey) -> Result<()> {
+        self.authorize(&key)?;
+        self.client.clear();
+        Ok(())
+    }
+
+    #[oai(path = "/cache/player", method = "delete")]
+    async fn evict_player(
+        &self,
+        key: AdminKey,
+        Query(name): Query<String>,
+        Query(number): Query<u64>,
+    ) -> Result<()> {
+        self.authorize(&key)?;
+        let btag = battletag(name, number)?;
+        if self.client.evict_player(&btag) {
+            Ok(())
+        } else {
+            Err(Error::not_found("Player is not cached"))
+        }
+    }
+
+    #[oai(path = "/cache/search", method = "delete")]
+    async fn evict_search(&self, key: AdminKey, Query(name): Query<String>) -> Result<()> {
+        self.authorize(&key)?;
+        if self.client.evict_search(&name) {
+            Ok(())
+        } else {
+            Err(Error::not_found("Search is not cached"))
+        }
+    }
+
+    #[oai(path = "/cache/player/refresh", method = "post")]
+    async fn refresh_player(
+        &self,
+        key: AdminKey,
+        Query(name): Query<String>,
+        Query(number): Query<u64>,
+    ) -> Result<Json<Arc<PlayerProfile>>> {
+        self.authorize(&key)?;
+        let btag = battletag(name, number)?;
+        Ok(Json(self.client.refresh_profile(&btag).await?))
+    }
+
+    #[oai(path = "/cache/warm", method = "post")]
+    async fn warm(
+        &self,
+        key: AdminKey,
+        Json(btags): Json<Vec<Battletag>>,
+    ) -> Result<Json<WarmReport>> {
+        self.authorize(&key)?;
+        Ok(Json(self.client.warm(&btags, self.warm_concurrency).await))
+    }
+
+    /// The selector configuration in use, as TOML.
+    #[oai(path = "/selectors", method = "get")]
+    async fn selectors(&self, key: AdminKey) -> Result<PlainText<String>> {
+        self.authorize(&key)?;
+        Ok(PlainText(self.client.selectors().to_toml()))
+    }
+
+    /// Reloads the selector configuration file and checks it against the fixture pages. It is
+    /// only applied if every check passes, and never on a dry run.
+    #[oai(path = "/selectors/reload", method = "post")]
+    async fn reload_selectors(
+        &self,
+        key: AdminKey,
+        Query(dry_run): Query<Option<bool>>,
+    ) -> Result<Json<SelectorsReport>> {
+        self.authorize(&key)?;
+        let path = self
+            .selectors
+            .as_ref()
+            .ok_or_else(|| Error::not_found("No selector configuration is set"))?;
+        let selectors = match Selectors::load(path) {
+            Ok(selectors) => selectors,
+            Err(sombra::Error::Selectors(errors)) => {
+                return Ok(Json(SelectorsReport {
+                    errors,
+                    ..SelectorsReport::default()
+                }))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let errors = selectors.check_fixtures(&self.fixtures)?;
+        let applied = errors.is_empty() && !dry_run.unwrap_or(false);
+        let version = selectors.version;
+        if applied {
+            tracing::info!(?path, version, "Reloaded selectors");
+            self.client.set_selectors(selectors);
+        }
+        Ok(Json(SelectorsReport {
+            version: Some(version),
+            applied,
+            errors,
+        }))
+    }
+
+    /// Runs every parser against the canary pages in the fixture directory, fetching the fresh
+    /// ones only if `fresh` is set.
+    #[oai(path = "/canary", method = "post")]
+    async fn canary(
+        &self,
+        key: AdminKey,
+        Query(fresh): Query<Option<bool>>,
+    ) -> Result<Json<DriftReport>> {
+        self.authorize(&key)?;
+        let canary = Canary::load(&self.fixtures)?;
+        let report = self.client.canary(&canary, fresh.unwrap_or(false)).await;
+        Ok(Json(report))
+    }
+}
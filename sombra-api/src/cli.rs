@@ -0,0 +1,186 @@
+use std::{net::SocketAddr, path::PathBuf};
+
+use clap::{builder::FalseyValueParser, Parser, ValueEnum};
+
+use crate::Config;
+
+/// Serves the Sombra API, its documentation and the lookup frontend.
+///
+/// Every option can also be set through the environment variable shown with it. Other cache and
+/// upstream settings are read from the config file, see `sombra-api/config.example.toml`, whose
+/// values are overridden by the `--ttl-*` and `--upstream` options.
+#[derive(Debug, Clone, Parser)]
+#[command(version)]
+pub struct Cli {
+    /// Address to listen on
+    #[arg(long, env = "SOMBRA_BIND", default_value = "0.0.0.0:8000")]
+    pub bind: SocketAddr,
+    /// Built sombra-lookup frontend, e.g. by `trunk build` in `sombra-lookup`
+    #[arg(long, env = "SOMBRA_STATIC_DIR", default_value = "sombra-lookup/dist")]
+    pub static_dir: PathBuf,
+    #[arg(long, env = "SOMBRA_LOG_FORMAT", value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// TOML config file, whose values can be overridden by `SOMBRA__<TABLE>__<KEY>` variables
+    #[arg(long, env = "SOMBRA_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// Persists the cache in this directory
+    #[arg(long, env = "SOMBRA_CACHE_DIR")]
+    pub cache_dir: Option<PathBuf>,
+    /// Seconds until cached profiles expire, `cache.hard_ttl.profile` in the config
+    #[arg(long, env = "SOMBRA_TTL_PROFILE")]
+    pub ttl_profile: Option<u64>,
+    /// Seconds until cached Overbuff data expires, `cache.hard_ttl.overbuff` in the config
+    #[arg(long, env = "SOMBRA_TTL_OVERBUFF")]
+    pub ttl_overbuff: Option<u64>,
+    /// Seconds until cached search results expire, `cache.hard_ttl.search` in the config
+    #[arg(long, env = "SOMBRA_TTL_SEARCH")]
+    pub ttl_search: Option<u64>,
+    /// Mirror serving every upstream site, e.g. sombra-mock, `upstream.mirror` in the config
+    #[arg(long, env = "SOMBRA_UPSTREAM")]
+    pub upstream: Option<String>,
+
+    /// Records every upstream response in the layout sombra-mock serves
+    #[arg(long, env = "SOMBRA_RECORD_DIR")]
+    pub record_dir: Option<PathBuf>,
+    /// Replaces player names in recorded responses
+    #[arg(long, env = "SOMBRA_RECORD_REDACT", value_parser = FalseyValueParser::new())]
+    pub record_redact: bool,
+    /// Saves pages that fail to parse in this directory
+    #[arg(long, env = "SOMBRA_QUARANTINE_DIR")]
+    pub quarantine_dir: Option<PathBuf>,
+    /// Selector configuration replacing the built-in selectors, reloaded by the admin API
+    #[arg(long, env = "SOMBRA_SELECTORS")]
+    pub selectors: Option<PathBuf>,
+    /// Saved pages that reloaded selectors and the canary are checked against
+    #[arg(long, env = "SOMBRA_FIXTURES", default_value = "sombra/fixtures")]
+    pub fixtures: PathBuf,
+    /// Runs the canary every this many seconds
+    #[arg(long, env = "SOMBRA_CANARY_INTERVAL")]
+    pub canary_interval: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human readable lines
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+impl Cli {
+    /// Configuration from the environment only, for runtimes that do not pass arguments.
+    pub fn from_env() -> Self {
+        Self::parse_from([env!("CARGO_PKG_NAME")])
+    }
+
+    /// Overrides the config with the options. Soft TTLs are lowered to the hard TTLs given.
+    pub fn apply(&self, config: &mut Config) {
+        let cache = &mut config.cache;
+        let ttls = [
+            (
+                self.ttl_profile,
+                &mut cache.hard_ttl.profile,
+                &mut cache.soft_ttl.profile,
+            ),
+            (
+                self.ttl_overbuff,
+                &mut cache.hard_ttl.overbuff,
+                &mut cache.soft_ttl.overbuff,
+            ),
+            (
+                self.ttl_search,
+                &mut cache.hard_ttl.search,
+                &mut cache.soft_ttl.search,
+            ),
+        ];
+        for (ttl, hard, soft) in ttls {
+            if let Some(ttl) = ttl {
+                *hard = ttl;
+                *soft = (*soft).min(ttl);
+            }
+        }
+        if let Some(upstream) = &self.upstream {
+            config.upstream.mirror = Some(upstream.clone());
+        }
+    }
+}
+
+impl LogFormat {
+    /// Logs to stdout, filtered by `RUST_LOG` or at `info` level by default.
+    #[cfg(not(feature = "shuttle"))]
+    pub fn init(self) {
+        let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+        let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+        match self {
+            Self::Text => subscriber.init(),
+            Self::Json => subscriber.json().init(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn the_command_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn options_are_parsed() {
+        let cli = Cli::try_parse_from([
+            "sombra-api",
+            "--bind",
+            "127.0.0.1:9000",
+            "--log-format",
+            "json",
+            "--record-redact",
+            "--ttl-profile",
+            "600",
+            "--upstream",
+            "http://127.0.0.1:8001",
+        ])
+        .unwrap();
+        assert_eq!(cli.bind, "127.0.0.1:9000".parse().unwrap());
+        assert_eq!(cli.log_format, LogFormat::Json);
+        assert!(cli.record_redact);
+        assert_eq!((cli.ttl_profile, cli.ttl_search), (Some(600), None));
+        assert_eq!(cli.upstream.as_deref(), Some("http://127.0.0.1:8001"));
+
+        assert!(Cli::try_parse_from(["sombra-api", "--ttl-search", "soon"]).is_err());
+        assert!(Cli::try_parse_from(["sombra-api", "--log-format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn options_override_the_config() {
+        let cli = Cli::try_parse_from([
+            "sombra-api",
+            "--ttl-profile",
+            "600",
+            "--ttl-search",
+            "7200",
+            "--upstream",
+            "http://127.0.0.1:8001",
+        ])
+        .unwrap();
+        let defaults = Config::default().cache;
+        let config = Config::load(None, |config| cli.apply(config)).unwrap();
+        assert_eq!(config.cache.hard_ttl.profile, 600);
+        assert_eq!(config.cache.soft_ttl.profile, 600);
+        assert_eq!(config.cache.hard_ttl.search, 7200);
+        assert_eq!(config.cache.soft_ttl.search, defaults.soft_ttl.search);
+        assert_eq!(config.cache.hard_ttl.overbuff, defaults.hard_ttl.overbuff);
+        assert_eq!(
+            config.upstream.mirror.as_deref(),
+            Some("http://127.0.0.1:8001")
+        );
+
+        let cli = Cli::try_parse_from(["sombra-api", "--ttl-search", "0"]).unwrap();
+        assert!(Config::load(None, |config| cli.apply(config)).is_err());
+    }
+}
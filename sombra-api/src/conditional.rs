@@ -0,0 +1,347 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use poem::{
+    http::{
+        header::{self, HeaderName},
+        Method, StatusCode,
+    },
+    Endpoint, IntoResponse, Request, Response,
+};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// Gives successful `GET` responses without an `ETag` a strong one hashed from their body, and
+/// evaluates the preconditions of the request against it: `If-Match` or `If-Unmodified-Since`
+/// that no longer hold fail with `412 Precondition Failed`, `If-None-Match` or
+/// `If-Modified-Since` that are still current are answered with `304 Not Modified`.
+pub async fn conditional<E: Endpoint>(ep: Arc<E>, req: Request) -> poem::Result<Response> {
+    let is_get = matches!(*req.method(), Method::GET | Method::HEAD);
+    let if_match = header_str(&req, &header::IF_MATCH).map(str::to_owned);
+    let if_unmodified_since =
+        header_str(&req, &header::IF_UNMODIFIED_SINCE).and_then(parse_http_date);
+    let if_none_match = header_str(&req, &header::IF_NONE_MATCH).map(str::to_owned);
+    let if_modified_since = header_str(&req, &header::IF_MODIFIED_SINCE).and_then(parse_http_date);
+    let mut resp = ep.call(req).await?.into_response();
+    if !is_get || resp.status() != StatusCode::OK {
+        return Ok(resp);
+    }
+
+    if !resp.headers().contains_key(header::ETAG) {
+        let body = resp.take_body().into_bytes().await?;
+        let tag = etag(&body);
+        resp.set_body(body);
+        if let Ok(tag) = tag.parse() {
+            resp.headers_mut().insert(header::ETAG, tag);
+        }
+    }
+    let etag = resp
+        .headers()
+        .get(header::ETAG)
+        .and_then(|v| v.to_str().ok());
+    let last_modified = resp
+        .headers()
+        .get(header::LAST_MODIFIED)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_http_date);
+    // `If-Unmodified-Since` is ignored if `If-Match` is present
+    let failed = match (if_match, if_unmodified_since) {
+        (Some(if_match), _) => !etag.is_some_and(|etag| matches_strong(&if_match, etag)),
+        (None, Some(since)) => last_modified.is_some_and(|modified| modified > since),
+        (None, None) => false,
+    };
+    if failed {
+        return Ok(StatusCode::PRECONDITION_FAILED.into_response());
+    }
+    // `If-Modified-Since` is ignored if `If-None-Match` is present
+    let not_modified = match (if_none_match, if_modified_since) {
+        (Some(if_none_match), _) => etag.is_some_and(|etag| matches(&if_none_match, etag)),
+        (None, Some(since)) => last_modified.is_some_and(|modified| modified <= since),
+        (None, None) => false,
+    };
+    if !not_modified {
+        return Ok(resp);
+    }
+
+    // every header but those describing the omitted body, including the rate limits
+    let mut not_modified = Response::builder()
+        .status(StatusCode::NOT_MODIFIED)
+        .finish();
+    let omitted = [
+        header::CONTENT_TYPE,
+        header::CONTENT_LENGTH,
+        header::CONTENT_ENCODING,
+        header::CONTENT_LANGUAGE,
+    ];
+    for (name, value) in resp.headers() {
+        if !omitted.contains(name) {
+            not_modified.headers_mut().append(name, value.clone());
+        }
+    }
+    Ok(not_modified)
+}
+
+/// A strong entity tag for `body`, the same across builds and instances of the API. JSON bodies
+/// are hashed in a canonical form, as the keys of maps are serialized in arbitrary order.
+pub fn etag(body: &[u8]) -> String {
+    match serde_json::from_slice(body) {
+        Ok(value) => value_etag(&value),
+        Err(_) => tag(Sha256::new().chain_update(body)),
+    }
+}
+
+/// The tag of `value` as it is sent in a JSON response.
+pub fn json_etag<T: Serialize + ?Sized>(value: &T) -> String {
+    value_etag(&serde_json::to_value(value).expect("response is serializable"))
+}
+
+fn value_etag(value: &Value) -> String {
+    let mut hasher = Sha256::new();
+    hash_value(value, &mut hasher);
+    tag(hasher)
+}
+
+/// The first 128 bits of the hash, quoted.
+fn tag(hasher: Sha256) -> String {
+    let hash = hasher.finalize();
+    let hex: String = hash[..16].iter().map(|b| format!("{b:02x}")).collect();
+    format!("\"{hex}\"")
+}
+
+/// Hashes objects with their keys in order. Strings and containers are prefixed with their
+/// length so that different values cannot hash the same bytes.
+fn hash_value(value: &Value, hasher: &mut Sha256) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_unstable_by_key(|(key, _)| *key);
+            hasher.update(b"{");
+            hasher.update((entries.len() as u64).to_le_bytes());
+            for (key, value) in entries {
+                hash_str(key, hasher);
+                hash_value(value, hasher);
+            }
+        }
+        Value::Array(values) => {
+            hasher.update(b"[");
+            hasher.update((values.len() as u64).to_le_bytes());
+            for value in values {
+                hash_value(value, hasher);
+            }
+        }
+        value => hash_str(&value.to_string(), hasher),
+    }
+}
+
+fn hash_str(s: &str, hasher: &mut Sha256) {
+    hasher.update((s.len() as u64).to_le_bytes());
+    hasher.update(s);
+}
+
+/// Whether `etag` is one of the tags in an `If-None-Match` header, using weak comparison.
+pub fn matches(if_none_match: &str, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Whether `etag` is one of the tags in an `If-Match` header, using strong comparison.
+fn matches_strong(if_match: &str, etag: &str) -> bool {
+    if_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || (tag == etag && !etag.starts_with("W/")))
+}
+
+/// Formats `date` as an HTTP date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub fn http_date(date: DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn parse_http_date(date: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(date)
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+fn header_str<'a>(req: &'a Request, name: &HeaderName) -> Option<&'a str> {
+    req.headers().get(name).and_then(|v| v.to_str().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use poem::{endpoint::make_sync, test::TestClient, EndpointExt};
+
+    use super::*;
+
+    const BODY: &str = r#"{"name":"Player","ranks":[1,2]}"#;
+    const LAST_MODIFIED: &str = "Tue, 14 Nov 2023 22:13:20 GMT";
+    const EARLIER: &str = "Tue, 14 Nov 2023 22:13:19 GMT";
+
+    fn client() -> TestClient<impl Endpoint> {
+        let ep = make_sync(|_| {
+            Response::builder()
+                .header(header::LAST_MODIFIED, LAST_MODIFIED)
+                .header("x-cache", "HIT")
+                .header("ratelimit-remaining", "9")
+                .content_type("application/json")
+                .body(BODY)
+        });
+        TestClient::new(ep.around(conditional))
+    }
+
+    #[test]
+    fn json_tags_do_not_depend_on_key_order() {
+        assert_eq!(
+            etag(br#"{"a":1,"b":{"c":[1,2],"d":null}}"#),
+            etag(br#"{"b":{"d":null,"c":[1,2]},"a":1}"#)
+        );
+        assert_ne!(etag(br#"{"a":[1,2]}"#), etag(br#"{"a":[2,1]}"#));
+        assert_ne!(etag(br#"{"a":"1"}"#), etag(br#"{"a":1}"#));
+
+        let hash_map: HashMap<_, _> = (0..100).map(|i| (i.to_string(), i)).collect();
+        let btree_map: BTreeMap<_, _> = hash_map.clone().into_iter().collect();
+        assert_eq!(json_etag(&hash_map), json_etag(&btree_map));
+        assert_eq!(
+            json_etag(&hash_map),
+            etag(&serde_json::to_vec(&btree_map).unwrap())
+        );
+    }
+
+    #[test]
+    fn tags_do_not_depend_on_the_build() {
+        assert_eq!(
+            etag(BODY.as_bytes()),
+            r#""e0f13040d67670e7b3e5994618c3147e""#
+        );
+        assert_eq!(
+            etag(b"<html></html>"),
+            r#""b633a587c652d02386c4f16f8c6f6aab""#
+        );
+    }
+
+    #[test]
+    fn tags_are_compared_weakly_for_if_none_match() {
+        assert!(matches(r#""a", W/"b""#, r#""b""#));
+        assert!(matches("*", r#""b""#));
+        assert!(!matches(r#""a""#, r#""b""#));
+        assert!(matches_strong(r#""a", "b""#, r#""b""#));
+        assert!(!matches_strong(r#"W/"b""#, r#""b""#));
+        assert!(!matches_strong(r#""b""#, r#"W/"b""#));
+    }
+
+    #[tokio::test]
+    async fn responses_are_tagged() {
+        let resp = client().get("/").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_header(header::ETAG, etag(BODY.as_bytes()));
+        resp.assert_text(BODY).await;
+    }
+
+    #[tokio::test]
+    async fn current_tags_are_not_modified() {
+        let tag = etag(BODY.as_bytes());
+        let resp = client()
+            .get("/")
+            .header(header::IF_NONE_MATCH, format!(r#""other", {tag}"#))
+            .send()
+            .await;
+        resp.assert_status(StatusCode::NOT_MODIFIED);
+        resp.assert_header(header::ETAG, &tag);
+        resp.assert_header(header::LAST_MODIFIED, LAST_MODIFIED);
+        resp.assert_header("x-cache", "HIT");
+        resp.assert_header("ratelimit-remaining", "9");
+        resp.assert_header_is_not_exist(header::CONTENT_TYPE);
+        resp.assert_text("").await;
+
+        let resp = client()
+            .get("/")
+            .header(header::IF_NONE_MATCH, r#""other""#)
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+    }
+
+    #[tokio::test]
+    async fn unmodified_responses_are_not_modified() {
+        let resp = client()
+            .get("/")
+            .header(header::IF_MODIFIED_SINCE, LAST_MODIFIED)
+            .send()
+            .await;
+        resp.assert_status(StatusCode::NOT_MODIFIED);
+
+        let resp = client()
+            .get("/")
+            .header(header::IF_MODIFIED_SINCE, EARLIER)
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+
+        // `If-None-Match` takes precedence
+        let resp = client()
+            .get("/")
+            .header(header::IF_NONE_MATCH, r#""other""#)
+            .header(header::IF_MODIFIED_SINCE, LAST_MODIFIED)
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+    }
+
+    #[tokio::test]
+    async fn outdated_preconditions_fail() {
+        let tag = etag(BODY.as_bytes());
+        let resp = client()
+            .get("/")
+            .header(header::IF_MATCH, r#""other""#)
+            .send()
+            .await;
+        resp.assert_status(StatusCode::PRECONDITION_FAILED);
+
+        let resp = client()
+            .get("/")
+            .header(header::IF_MATCH, &tag)
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+
+        let resp = client()
+            .get("/")
+            .header(header::IF_UNMODIFIED_SINCE, EARLIER)
+            .send()
+            .await;
+        resp.assert_status(StatusCode::PRECONDITION_FAILED);
+
+        let resp = client()
+            .get("/")
+            .header(header::IF_UNMODIFIED_SINCE, LAST_MODIFIED)
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+
+        // `If-Match` takes precedence
+        let resp = client()
+            .get("/")
+            .header(header::IF_MATCH, "*")
+            .header(header::IF_UNMODIFIED_SINCE, EARLIER)
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+    }
+
+    #[tokio::test]
+    async fn other_methods_are_passed_through() {
+        let resp = client()
+            .post("/")
+            .header(header::IF_MATCH, r#""other""#)
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        assert!(!resp.0.headers().contains_key(header::ETAG));
+    }
+}
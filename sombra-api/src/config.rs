@@ -0,0 +1,685 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::PathBuf,
+};
+
+use poem::http::HeaderName;
+use serde_derive::{Deserialize, Serialize};
+use sombra::{Lifespans, Upstream, UpstreamLimits, BLIZZARD_URL, DEFAULT_CAPACITY, OVERBUFF_URL};
+use thiserror::Error;
+
+/// Prefix of the environment variables overriding configuration values, e.g.
+/// `SOMBRA__CACHE__SOFT_TTL__PROFILE=60` for `soft_ttl.profile` in the `[cache]` table.
+const ENV_PREFIX: &str = "SOMBRA__";
+const ENV_SEPARATOR: &str = "__";
+
+/// Settings of the API, read from a TOML file in which every value is optional. See
+/// [`load`](Self::load) for how they are combined with the defaults and the environment.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub cache: CacheConfig,
+    pub upstream: UpstreamConfig,
+    pub limits: LimitsConfig,
+    pub overbuff: OverbuffConfig,
+    pub admin: AdminConfig,
+    pub rate_limit: RateLimitConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Entries kept before the least recently used one is evicted
+    pub capacity: usize,
+    /// Seconds after which values are refreshed in the background
+    pub soft_ttl: Ttls,
+    /// Seconds after which values are dropped
+    pub hard_ttl: Ttls,
+}
+
+/// Seconds per kind of cached data
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Ttls {
+    pub profile: u64,
+    pub overbuff: u64,
+    pub search: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpstreamConfig {
+    /// Serves every site from this base URL with the host of the site as the first path
+    /// segment, e.g. a sombra-mock server. Takes precedence over the URLs of the sites.
+    pub mirror: Option<String>,
+    pub blizzard: String,
+    pub overbuff: String,
+    /// Requests started per second
+    pub requests_per_second: Option<u32>,
+    /// Requests in flight at once
+    pub concurrency: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// Battletags accepted in one batch request
+    pub batch_size: usize,
+    /// Players of a batch fetched at once
+    pub batch_concurrency: usize,
+    /// Players fetched at once when warming the cache
+    pub warm_concurrency: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OverbuffConfig {
+    /// Whether Overbuff is scraped. If not, its endpoint fails and ranks come from the career
+    /// page only.
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Key expected in the `X-Admin-Key` header. The admin API is disabled if unset.
+    pub key: Option<String>,
+}
+
+/// Token buckets limiting the requests of every client, identified by API key or IP address.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Header that API keys are sent in
+    pub key_header: String,
+    /// Number of proxies in front of the API that append to `X-Forwarded-For`. Clients are
+    /// identified by the address that many entries from its end, 0 ignores the header.
+    pub trusted_proxies: usize,
+    /// Bucket shared by every endpoint without its own
+    pub default: BucketConfig,
+    /// Buckets of endpoints by path below `/api`, or its start if it ends with `*`. Exact paths
+    /// take precedence over prefixes, longer prefixes over shorter ones. Values missing from them
+    /// are taken from `default`.
+    pub endpoints: BTreeMap<String, BucketConfig>,
+    /// Accepted API keys by the name of their owner. Requests with any other key are rejected.
+    pub keys: BTreeMap<String, ApiKeyConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKeyConfig {
+    pub key: String,
+    /// Applied to the capacity and refill rate of the buckets of the key
+    #[serde(default = "ApiKeyConfig::default_factor")]
+    pub factor: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BucketConfig {
+    /// Tokens the bucket holds when full, i.e. the allowed burst
+    pub capacity: u32,
+    /// Tokens added per second
+    pub refill_per_second: f64,
+    /// Tokens taken by a request served from the cache
+    pub hit_cost: u32,
+    /// Tokens taken by a request that fetched from upstream, including the `hit_cost`
+    pub fetch_cost: u32,
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Could not read config file {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+    #[error("Invalid config: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Invalid config: {0}")]
+    Invalid(String),
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_CAPACITY,
+            soft_ttl: Ttls::all(60 * 20),
+            hard_ttl: Ttls::all(60 * 60 * 6),
+        }
+    }
+}
+
+impl Default for UpstreamConfig {
+    fn default() -> Self {
+        Self {
+            mirror: None,
+            blizzard: BLIZZARD_URL.to_owned(),
+            overbuff: OVERBUFF_URL.to_owned(),
+            requests_per_second: None,
+            concurrency: None,
+        }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 32,
+            batch_concurrency: 4,
+            warm_concurrency: 4,
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            key_header: "X-Api-Key".to_owned(),
+            trusted_proxies: 0,
+            default: BucketConfig::default(),
+            endpoints: BTreeMap::new(),
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for BucketConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 60,
+            refill_per_second: 1.0,
+            hit_cost: 1,
+            fetch_cost: 5,
+        }
+    }
+}
+
+impl Default for OverbuffConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+impl Ttls {
+    pub const fn all(secs: u64) -> Self {
+        Self {
+            profile: secs,
+            overbuff: secs,
+            search: secs,
+        }
+    }
+
+    pub const fn lifespans(self) -> Lifespans {
+        Lifespans::new(self.profile, self.overbuff, self.search)
+    }
+
+    fn by_kind(self) -> [(&'static str, u64); 3] {
+        [
+            ("profile", self.profile),
+            ("overbuff", self.overbuff),
+            ("search", self.search),
+        ]
+    }
+}
+
+impl UpstreamConfig {
+    pub fn upstream(&self) -> Upstream {
+        match &self.mirror {
+            Some(base) => Upstream::mirror(base),
+            None => Upstream {
+                blizzard: self.blizzard.trim_end_matches('/').to_owned(),
+                overbuff: self.overbuff.trim_end_matches('/').to_owned(),
+            },
+        }
+    }
+
+    pub const fn limits(&self) -> UpstreamLimits {
+        UpstreamLimits {
+            requests_per_second: self.requests_per_second,
+            concurrency: self.concurrency,
+        }
+    }
+}
+
+impl Config {
+    /// Applies the values in `file`, if given, and then those in the environment to the defaults,
+    /// lets `customize` change the result, e.g. by command line options, and validates it.
+    ///
+    /// A variable `SOMBRA__<TABLE>__<KEY>` sets `key` in `[table]`, with nested tables
+    /// separated by further `__`. Values are parsed as TOML and taken as strings if that fails
+    /// or the setting only accepts strings, e.g. `SOMBRA__ADMIN__KEY=123456`.
+    pub fn load(
+        file: Option<PathBuf>,
+        customize: impl FnOnce(&mut Self),
+    ) -> Result<Self, ConfigError> {
+        let toml = match &file {
+            Some(path) => {
+                fs::read_to_string(path).map_err(|e| ConfigError::Read(path.clone(), e))?
+            }
+            None => String::new(),
+        };
+        let mut config = Self::from_sources(&toml, std::env::vars())?;
+        customize(&mut config);
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Applies `toml` and then the variables in `vars` that start with [`ENV_PREFIX`], without
+    /// validating the result.
+    fn from_sources(
+        toml: &str,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let defaults = toml::Value::try_from(Self::default()).expect("config is serializable");
+        let toml::Value::Table(mut table) = defaults else {
+            unreachable!("config is serialized as a table");
+        };
+        merge(&mut table, toml.parse()?);
+        let file = table.clone();
+        for (name, value) in vars {
+            if let Some(path) = name.strip_prefix(ENV_PREFIX) {
+                let value = env_value(&file, &name, path, &value)?;
+                set_path(&mut table, &name, path, value)?;
+            }
+        }
+        inherit_default_bucket(&mut table);
+        Ok(toml::Value::Table(table).try_into()?)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let mut errors = Vec::new();
+        if self.cache.capacity == 0 {
+            errors.push("cache.capacity must be at least 1".to_owned());
+        }
+        let (soft, hard) = (self.cache.soft_ttl.by_kind(), self.cache.hard_ttl.by_kind());
+        for ((kind, soft), (_, hard)) in soft.into_iter().zip(hard) {
+            if hard == 0 {
+                errors.push(format!("cache.hard_ttl.{kind} must be at least 1"));
+            }
+            if soft > hard {
+                errors.push(format!(
+                    "cache.soft_ttl.{kind} ({soft}) must not exceed cache.hard_ttl.{kind} ({hard})"
+                ));
+            }
+        }
+
+        let mut urls = vec![
+            ("upstream.blizzard", self.upstream.blizzard.as_str()),
+            ("upstream.overbuff", self.upstream.overbuff.as_str()),
+        ];
+        urls.extend(
+            self.upstream
+                .mirror
+                .as_deref()
+                .map(|url| ("upstream.mirror", url)),
+        );
+        for (name, url) in urls {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                errors.push(format!("{name} must be an http or https URL, not {url:?}"));
+            }
+        }
+        if self.upstream.requests_per_second == Some(0) {
+            errors.push("upstream.requests_per_second must be at least 1".to_owned());
+        }
+        if self.upstream.concurrency == Some(0) {
+            errors.push("upstream.concurrency must be at least 1".to_owned());
+        }
+
+        let limits = [
+            ("limits.batch_size", self.limits.batch_size),
+            ("limits.batch_concurrency", self.limits.batch_concurrency),
+            ("limits.warm_concurrency", self.limits.warm_concurrency),
+        ];
+        for (name, limit) in limits {
+            if limit == 0 {
+                errors.push(format!("{name} must be at least 1"));
+            }
+        }
+        if self
+            .admin
+            .key
+            .as_ref()
+            .is_some_and(|key| key.trim().is_empty())
+        {
+            errors.push("admin.key must not be empty".to_owned());
+        }
+        self.rate_limit.validate(&mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(errors.join(", ")))
+        }
+    }
+}
+
+impl RateLimitConfig {
+    fn validate(&self, errors: &mut Vec<String>) {
+        if HeaderName::try_from(self.key_header.as_str()).is_err() {
+            errors.push(format!(
+                "rate_limit.key_header is not a header name: {:?}",
+                self.key_header
+            ));
+        }
+        self.default.validate("rate_limit.default", errors);
+        for (path, bucket) in &self.endpoints {
+            if !path.starts_with('/') {
+                errors.push(format!("rate_limit.endpoints: {path:?} must start with /"));
+            }
+            bucket.validate(&format!("rate_limit.endpoints.{path:?}"), errors);
+        }
+        let mut keys = HashSet::new();
+        for (name, key) in &self.keys {
+            if key.key.is_empty() {
+                errors.push(format!("rate_limit.keys.{name}.key must not be empty"));
+            }
+            if !keys.insert(&key.key) {
+                errors.push(format!("rate_limit.keys.{name}.key is used more than once"));
+            }
+            if key.factor.is_nan() || key.factor <= 0.0 {
+                errors.push(format!("rate_limit.keys.{name}.factor must be above 0"));
+            }
+            // a bucket scaled below a fetch would always be overdrawn by one
+            let buckets = std::iter::once(("default", &self.default))
+                .chain(self.endpoints.iter().map(|(path, b)| (path.as_str(), b)));
+            for (bucket_name, bucket) in buckets {
+                if f64::from(bucket.capacity) * key.factor < f64::from(bucket.fetch_cost) {
+                    errors.push(format!(
+                        "rate_limit.keys.{name}.factor scales the capacity of {bucket_name:?} \
+                         below its fetch_cost"
+                    ));
+                }
+            }
+        }
+    }
+}
+
+impl ApiKeyConfig {
+    const fn default_factor() -> f64 {
+        1.0
+    }
+}
+
+impl BucketConfig {
+    fn validate(&self, name: &str, errors: &mut Vec<String>) {
+        if self.capacity == 0 {
+            errors.push(format!("{name}.capacity must be at least 1"));
+        }
+        if self.refill_per_second.is_nan() || self.refill_per_second <= 0.0 {
+            errors.push(format!("{name}.refill_per_second must be above 0"));
+        }
+        if self.hit_cost > self.capacity {
+            errors.push(format!("{name}.hit_cost must not exceed the capacity"));
+        }
+        if self.fetch_cost > self.capacity {
+            errors.push(format!("{name}.fetch_cost must not exceed the capacity"));
+        }
+        if self.fetch_cost < self.hit_cost {
+            errors.push(format!("{name}.fetch_cost must not be below the hit_cost"));
+        }
+    }
+}
+
+/// Replaces the values in `base` with those in `overlay`, keeping what `overlay` lacks.
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Fills in the values missing from the buckets in `[rate_limit.endpoints]` with those in
+/// `[rate_limit.default]`.
+fn inherit_default_bucket(table: &mut toml::Table) {
+    let Some(toml::Value::Table(rate_limit)) = table.get_mut("rate_limit") else {
+        return;
+    };
+    let Some(toml::Value::Table(default)) = rate_limit.get("default").cloned() else {
+        return;
+    };
+    let Some(toml::Value::Table(endpoints)) = rate_limit.get_mut("endpoints") else {
+        return;
+    };
+    for (_, bucket) in endpoints.iter_mut() {
+        if let toml::Value::Table(bucket) = bucket {
+            let values = std::mem::replace(bucket, default.clone());
+            merge(bucket, values);
+        }
+    }
+}
+
+/// The value of the variable `name` for the setting at `path`: `value` parsed as TOML, or as a
+/// string if it is not valid TOML or if only the string is accepted when the setting is applied
+/// to `base` on its own.
+fn env_value(
+    base: &toml::Table,
+    name: &str,
+    path: &str,
+    value: &str,
+) -> Result<toml::Value, ConfigError> {
+    let string = toml::Value::String(value.to_owned());
+    let Some(parsed) = format!("value = {value}")
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut parsed| parsed.remove("value"))
+        .filter(|parsed| !parsed.is_str())
+    else {
+        return Ok(string);
+    };
+    let accepts = |value: &toml::Value| -> Result<bool, ConfigError> {
+        let mut table = base.clone();
+        set_path(&mut table, name, path, value.clone())?;
+        inherit_default_bucket(&mut table);
+        Ok(toml::Value::Table(table).try_into::<Config>().is_ok())
+    };
+    if !accepts(&parsed)? && accepts(&string)? {
+        Ok(string)
+    } else {
+        Ok(parsed)
+    }
+}
+
+/// Sets the value at `path`, relative to `table` and separated by [`ENV_SEPARATOR`].
+fn set_path(
+    table: &mut toml::Table,
+    name: &str,
+    path: &str,
+    value: toml::Value,
+) -> Result<(), ConfigError> {
+    let keys: Vec<String> = path.split(ENV_SEPARATOR).map(str::to_lowercase).collect();
+    let Some((last, tables)) = keys.split_last() else {
+        return Ok(());
+    };
+    let mut table = table;
+    for key in tables {
+        let entry = table
+            .entry(key.clone())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        table = entry
+            .as_table_mut()
+            .ok_or_else(|| ConfigError::Invalid(format!("{name}: {key} is not a table")))?;
+    }
+    table.insert(last.clone(), value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(toml: &str, vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let vars = vars
+            .iter()
+            .map(|(name, value)| ((*name).to_owned(), (*value).to_owned()));
+        let config = Config::from_sources(toml, vars)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn invalid(toml: &str) -> String {
+        match load(toml, &[]) {
+            Err(ConfigError::Invalid(errors)) => errors,
+            result => panic!("{toml:?} was not rejected by validation: {result:?}"),
+        }
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        let config = load("", &[]).unwrap();
+        assert_eq!(config.cache.capacity, DEFAULT_CAPACITY);
+        assert_eq!(config.upstream.blizzard, BLIZZARD_URL);
+        assert!(config.admin.key.is_none());
+        assert!(config.overbuff.enabled);
+    }
+
+    #[test]
+    fn the_file_overrides_defaults_and_the_environment_overrides_the_file() {
+        let toml = "
+            [cache]
+            capacity = 10
+            [cache.soft_ttl]
+            profile = 30
+            [limits]
+            batch_size = 8
+        ";
+        let config = load(
+            toml,
+            &[
+                ("SOMBRA__CACHE__CAPACITY", "20"),
+                ("SOMBRA__CACHE__HARD_TTL__SEARCH", "7200"),
+                ("OTHER__CACHE__CAPACITY", "30"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.cache.capacity, 20);
+        assert_eq!(config.cache.soft_ttl.profile, 30);
+        assert_eq!(
+            config.cache.soft_ttl.search,
+            CacheConfig::default().soft_ttl.search
+        );
+        assert_eq!(config.cache.hard_ttl.search, 7200);
+        assert_eq!(config.limits.batch_size, 8);
+        assert_eq!(config.limits.batch_concurrency, 4);
+    }
+
+    #[test]
+    fn environment_values_are_strings_where_needed() {
+        let config = load(
+            "",
+            &[
+                ("SOMBRA__ADMIN__KEY", "123456"),
+                ("SOMBRA__RATE_LIMIT__KEYS__ALICE__KEY", "42"),
+                ("SOMBRA__RATE_LIMIT__KEYS__ALICE__FACTOR", "2.5"),
+                ("SOMBRA__RATE_LIMIT__TRUSTED_PROXIES", "1"),
+                ("SOMBRA__UPSTREAM__MIRROR", "http://127.0.0.1:8001"),
+                ("SOMBRA__UPSTREAM__CONCURRENCY", "2"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.admin.key.as_deref(), Some("123456"));
+        assert_eq!(config.rate_limit.keys["alice"].key, "42");
+        assert!((config.rate_limit.keys["alice"].factor - 2.5).abs() < f64::EPSILON);
+        assert_eq!(config.rate_limit.trusted_proxies, 1);
+        assert_eq!(
+            config.upstream.mirror.as_deref(),
+            Some("http://127.0.0.1:8001")
+        );
+        assert_eq!(config.upstream.concurrency, Some(2));
+    }
+
+    #[test]
+    fn mistyped_environment_values_are_rejected() {
+        assert!(matches!(
+            load("", &[("SOMBRA__CACHE__CAPACITY", "many")]),
+            Err(ConfigError::Toml(_))
+        ));
+        assert!(matches!(
+            load("", &[("SOMBRA__CACHE__CAPACITY__MAX", "1")]),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        assert!(matches!(
+            load("[cache]\ncapasity = 10", &[]),
+            Err(ConfigError::Toml(_))
+        ));
+        assert!(matches!(
+            Config::load(Some("missing.toml".into()), |_| {}),
+            Err(ConfigError::Read(..))
+        ));
+    }
+
+    #[test]
+    fn endpoint_buckets_inherit_the_default() {
+        let toml = r#"
+            [rate_limit.default]
+            capacity = 100
+            fetch_cost = 10
+            [rate_limit.endpoints."/v1/search"]
+            capacity = 20
+        "#;
+        let config = load(toml, &[("SOMBRA__RATE_LIMIT__DEFAULT__HIT_COST", "2")]).unwrap();
+        let bucket = config.rate_limit.endpoints["/v1/search"];
+        assert_eq!(bucket.capacity, 20);
+        assert_eq!((bucket.hit_cost, bucket.fetch_cost), (2, 10));
+    }
+
+    #[test]
+    fn every_invalid_setting_is_reported() {
+        let errors = invalid(
+            r#"
+            [cache]
+            capacity = 0
+            [cache.soft_ttl]
+            profile = 100
+            [cache.hard_ttl]
+            profile = 50
+            [upstream]
+            mirror = "127.0.0.1:8001"
+        "#,
+        );
+        assert_eq!(
+            errors,
+            "cache.capacity must be at least 1, \
+             cache.soft_ttl.profile (100) must not exceed cache.hard_ttl.profile (50), \
+             upstream.mirror must be an http or https URL, not \"127.0.0.1:8001\""
+        );
+    }
+
+    #[test]
+    fn invalid_rate_limits_are_rejected() {
+        let errors = invalid(
+            r#"
+            [rate_limit]
+            key_header = "X Api Key"
+            [rate_limit.default]
+            hit_cost = 100
+            [rate_limit.endpoints.search]
+            refill_per_second = 0
+            [rate_limit.keys]
+            alice = { key = "same" }
+            bob = { key = "same", factor = 0 }
+            carol = { key = "small", factor = 0.05 }
+        "#,
+        );
+        for error in [
+            "rate_limit.key_header",
+            "rate_limit.default.hit_cost",
+            "\"search\" must start with /",
+            "rate_limit.endpoints.\"search\".refill_per_second",
+            "rate_limit.keys.bob.key is used more than once",
+            "rate_limit.keys.bob.factor",
+            "rate_limit.keys.carol.factor scales the capacity of \"default\" below its fetch_cost",
+        ] {
+            assert!(errors.contains(error), "{error} is missing from {errors}");
+        }
+        assert!(invalid("[admin]\nkey = \" \"").contains("admin.key"));
+    }
+}
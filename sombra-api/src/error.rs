@@ -1,29 +1,216 @@
 use poem::http::StatusCode;
-use poem_openapi::ApiResponse;
+use poem_openapi::{payload::Json, ApiResponse};
+use sombra::{ApiError, ErrorCode};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug, ApiResponse)]
 pub enum Error {
-    #[oai(status = 500)]
-    Internal,
+    /// Malformed request or invalid battletag
+    #[oai(status = 400)]
+    BadRequest(Json<ApiError>),
+    #[oai(status = 401)]
+    Unauthorized(Json<ApiError>),
+    /// The requested data is hidden on a private profile
+    #[oai(status = 403)]
+    Forbidden(Json<ApiError>),
     #[oai(status = 404)]
-    NotFound,
+    NotFound(Json<ApiError>),
+    /// `Retry-After` is the number of seconds to wait, if known.
+    #[oai(status = 429)]
+    TooManyRequests(Json<ApiError>, #[oai(header = "Retry-After")] Option<u64>),
+    #[oai(status = 500)]
+    Internal(Json<ApiError>),
+    /// Upstream failed or sent something that could not be parsed
+    #[oai(status = 502)]
+    BadGateway(Json<ApiError>),
+    #[oai(status = 504)]
+    GatewayTimeout(Json<ApiError>),
+}
+
+fn body(code: ErrorCode, message: impl Into<String>, retryable: bool) -> Json<ApiError> {
+    Json(ApiError {
+        code,
+        message: message.into(),
+        retryable,
+    })
+}
+
+impl Error {
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::BadRequest(body(ErrorCode::BadRequest, message, false))
+    }
+
+    pub fn invalid_battletag(battletag: impl std::fmt::Display) -> Self {
+        let message = format!("Invalid battletag: {battletag}");
+        Self::BadRequest(body(ErrorCode::InvalidBattletag, message, false))
+    }
+
+    pub fn unauthorized() -> Self {
+        Self::Unauthorized(body(ErrorCode::Unauthorized, "Invalid admin key", false))
+    }
+
+    pub fn invalid_api_key() -> Self {
+        Self::Unauthorized(body(ErrorCode::Unauthorized, "Invalid API key", false))
+    }
+
+    /// The client sent too many requests to the API, as opposed to the API to upstream.
+    pub fn too_many_requests(retry_after: u64) -> Self {
+        let message = "Too many requests, see the RateLimit headers";
+        Self::TooManyRequests(
+            body(ErrorCode::RateLimited, message, true),
+            Some(retry_after),
+        )
+    }
+
+    pub fn private_profile() -> Self {
+        let message = "The profile of this player is private";
+        Self::Forbidden(body(ErrorCode::PrivateProfile, message, false))
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::NotFound(body(ErrorCode::NotFound, message, false))
+    }
 }
 
 impl From<sombra::Error> for Error {
     fn from(e: sombra::Error) -> Self {
-        match e {
-            sombra::Error::Http(StatusCode::NOT_FOUND) => Self::NotFound,
-            sombra::Error::Http(_)
-            | sombra::Error::Request(_)
-            | sombra::Error::Deserializer(_)
+        let message = e.root().to_string();
+        match e.root() {
+            sombra::Error::Http(StatusCode::NOT_FOUND) => Self::not_found("Player not found"),
+            sombra::Error::RateLimited(retry_after) => {
+                Self::TooManyRequests(body(ErrorCode::RateLimited, message, true), *retry_after)
+            }
+            sombra::Error::Http(status) => {
+                let retryable = status.is_server_error();
+                Self::BadGateway(body(ErrorCode::Upstream, message, retryable))
+            }
+            sombra::Error::Request(request) if request.is_timeout() => {
+                Self::GatewayTimeout(body(ErrorCode::Timeout, message, true))
+            }
+            sombra::Error::Request(_) => Self::BadGateway(body(ErrorCode::Upstream, message, true)),
+            sombra::Error::Deserializer(_)
             | sombra::Error::Html(_)
             | sombra::Error::Battletag(_)
             | sombra::Error::Parse => {
+                tracing::warn!(error = ?e, "upstream parse error");
+                Self::BadGateway(body(ErrorCode::UpstreamParse, message, false))
+            }
+            sombra::Error::UnknownSource(_) => Self::not_found(message),
+            sombra::Error::Selectors(_)
+            | sombra::Error::Canary(_)
+            | sombra::Error::Io(_)
+            | sombra::Error::Shared(_) => {
                 tracing::error!(error = ?e, "internal error");
-                Self::Internal
+                Self::Internal(body(ErrorCode::Internal, "Internal error", false))
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use poem::IntoResponse;
+
+    use super::*;
+
+    /// Status, `Retry-After` header and body of the response to `e`.
+    fn respond(e: sombra::Error) -> (u16, Option<String>, ApiError) {
+        let error = Error::from(e);
+        let body = match &error {
+            Error::BadRequest(Json(body))
+            | Error::Unauthorized(Json(body))
+            | Error::Forbidden(Json(body))
+            | Error::NotFound(Json(body))
+            | Error::TooManyRequests(Json(body), _)
+            | Error::Internal(Json(body))
+            | Error::BadGateway(Json(body))
+            | Error::GatewayTimeout(Json(body)) => body.clone(),
+        };
+        let response = error.into_response();
+        let retry_after = response
+            .headers()
+            .get("retry-after")
+            .map(|value| value.to_str().unwrap().to_owned());
+        (response.status().as_u16(), retry_after, body)
+    }
+
+    #[test]
+    fn missing_players_are_not_found() {
+        let (status, _, body) = respond(sombra::Error::Http(StatusCode::NOT_FOUND));
+        assert_eq!(status, 404);
+        assert_eq!(body.code, ErrorCode::NotFound);
+        assert!(!body.retryable);
+    }
+
+    #[test]
+    fn upstream_rate_limits_are_passed_on() {
+        let (status, retry_after, body) = respond(sombra::Error::RateLimited(Some(60)));
+        assert_eq!((status, retry_after.as_deref()), (429, Some("60")));
+        assert_eq!(body.code, ErrorCode::RateLimited);
+        assert!(body.retryable);
+
+        let (status, retry_after, _) = respond(sombra::Error::RateLimited(None));
+        assert_eq!((status, retry_after), (429, None));
+    }
+
+    #[test]
+    fn upstream_failures_are_bad_gateways() {
+        let (status, _, body) = respond(sombra::Error::Http(StatusCode::SERVICE_UNAVAILABLE));
+        assert_eq!(status, 502);
+        assert_eq!(body.code, ErrorCode::Upstream);
+        assert!(body.retryable);
+
+        let (status, _, body) = respond(sombra::Error::Http(StatusCode::FORBIDDEN));
+        assert_eq!(status, 502);
+        assert!(!body.retryable);
+    }
+
+    #[test]
+    fn unparsable_pages_are_bad_gateways() {
+        let json = serde_json::from_str::<u8>("x").unwrap_err();
+        for error in [
+            sombra::Error::Parse,
+            sombra::Error::Battletag("Player".to_owned()),
+            sombra::Error::Deserializer(json),
+        ] {
+            let (status, _, body) = respond(error);
+            assert_eq!(status, 502);
+            assert_eq!(body.code, ErrorCode::UpstreamParse);
+            assert!(!body.retryable);
+        }
+    }
+
+    #[test]
+    fn internal_errors_are_not_described() {
+        for error in [
+            sombra::Error::Io(std::io::Error::other("disk full")),
+            sombra::Error::Selectors(vec!["Invalid selector".to_owned()]),
+            sombra::Error::Canary("missing file".to_owned()),
+        ] {
+            let (status, _, body) = respond(error);
+            assert_eq!(status, 500);
+            assert_eq!(body.code, ErrorCode::Internal);
+            assert_eq!(body.message, "Internal error");
+        }
+    }
+
+    #[test]
+    fn unknown_sources_are_not_found() {
+        let (status, _, body) = respond(sombra::Error::UnknownSource("other".to_owned()));
+        assert_eq!(status, 404);
+        assert_eq!(body.message, "Unknown data source: other");
+    }
+
+    #[test]
+    fn shared_errors_are_mapped_by_their_root() {
+        let shared = sombra::Error::Shared(Arc::new(sombra::Error::Shared(Arc::new(
+            sombra::Error::RateLimited(Some(5)),
+        ))));
+        let (status, retry_after, body) = respond(shared);
+        assert_eq!((status, retry_after.as_deref()), (429, Some("5")));
+        assert_eq!(body.message, "Rate limited by upstream");
+    }
+}
@@ -0,0 +1,394 @@
+//! The Sombra API, its documentation and the lookup frontend, served by the `sombra-api` binary
+//! or in-process with [`spawn`].
+
+#![allow(clippy::unused_async)]
+#![allow(clippy::useless_let_if_seq)]
+
+mod admin;
+mod cli;
+mod conditional;
+mod config;
+mod error;
+mod ratelimit;
+mod response;
+mod v2;
+
+pub use cli::{Cli, LogFormat};
+pub use config::Config;
+
+use admin::AdminApi;
+use config::LimitsConfig;
+use error::{Error, Result};
+use ratelimit::RateLimiter;
+use response::{CachedResponse, CombinedResponse, StaticResponse};
+use v2::V2Api;
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use poem::{
+    endpoint::StaticFilesEndpoint,
+    listener::{Acceptor, Listener, TcpListener},
+    middleware, Endpoint, EndpointExt, Route, Server,
+};
+use poem_openapi::{
+    param::{Header, Query},
+    payload::Json,
+    ContactObject, OpenApi, OpenApiService, Tags,
+};
+use sombra::{
+    Asset, BatchRequest, BatchResponse, Battletag, CacheMetrics, CachedClient, Canary, Client,
+    FoundPlayer, Hero, Id, LruStore, Mode, Overbuff, OverbuffSource, Platform, PlayerProfile,
+    PlayerProfileReduced, PlayerSummary, ProfileExpiry, ProfilePart, ProfileParts, Quarantine,
+    Recorder, Selectors,
+};
+
+pub type StartupResult<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+struct Api {
+    client: Arc<CachedClient>,
+    limits: LimitsConfig,
+    assets_etag: String,
+    heroes_etag: String,
+}
+
+#[derive(Tags)]
+enum ApiTags {
+    V1,
+    V2,
+    Admin,
+}
+
+#[OpenApi(prefix_path = "/v1", tag = "ApiTags::V1")]
+impl Api {
+    async fn new(cli: &Cli, config: &Config) -> sombra::Result<Self> {
+        let cache = &config.cache;
+        let lifespans = cache.hard_ttl.lifespans();
+        let store = match &cli.cache_dir {
+            Some(dir) => LruStore::persistent(lifespans, cache.capacity, dir)?,
+            None => LruStore::new(lifespans, cache.capacity),
+        };
+        let mut client = Client::with_upstream(config.upstream.upstream())
+            .await?
+            .limit(config.upstream.limits());
+        if let Some(dir) = &cli.record_dir {
+            let recorder = Recorder::new(dir)?.redact(cli.record_redact);
+            client.set_recorder(Some(recorder));
+            // the recording has to contain the pages fetched on startup to be replayable
+            client.fetch_assets().await?;
+            client.fetch_heroes().await?;
+        }
+        if !config.overbuff.enabled {
+            client.unregister_source(OverbuffSource::NAME);
+        }
+        let client = CachedClient::with_client(client, store)
+            .stale_while_revalidate(cache.soft_ttl.lifespans())
+            .profile_expiry(ProfileExpiry::LastUpdated);
+        let client = match &cli.quarantine_dir {
+            Some(dir) => client.quarantine(Quarantine::new(dir)?),
+            None => client,
+        };
+        if let Some(path) = &cli.selectors {
+            client.set_selectors(Selectors::load(path)?);
+        }
+        // assets and heroes are only fetched on startup
+        let assets_etag = conditional::json_etag(client.assets());
+        let heroes_etag = conditional::json_etag(client.heroes());
+        let client = Arc::new(client);
+        tokio::spawn(flush_cache(Arc::downgrade(&client)));
+        Ok(Self {
+            client,
+            limits: config.limits.clone(),
+            assets_etag,
+            heroes_etag,
+        })
+    }
+
+    #[oai(path = "/search", method = "get")]
+    async fn search(
+        &self,
+        Query(name): Query<String>,
+    ) -> Result<CachedResponse<Arc<Vec<FoundPlayer>>>> {
+        Ok(self.client.search(&name).await?.into())
+    }
+
+    #[oai(path = "/profile", method = "get")]
+    async fn profile(
+        &self,
+        Query(name): Query<String>,
+        Query(number): Query<u64>,
+    ) -> Result<CachedResponse<PlayerProfileReduced>> {
+        let btag = battletag(name, number)?;
+        let profile = self.client.profile(&btag).await?;
+        let last_updated = profile.value.last_updated;
+        Ok(CachedResponse::from(profile).last_modified(last_updated))
+    }
+
+    /// Only the requested `parts` are parsed. All of them if none are given.
+    #[oai(path = "/profile_full", method = "get")]
+    async fn profile_full(
+        &self,
+        Query(name): Query<String>,
+        Query(number): Query<u64>,
+        Query(parts): Query<Vec<ProfilePart>>,
+    ) -> Result<CachedResponse<Arc<PlayerProfile>>> {
+        let btag = battletag(name, number)?;
+        let parts = if parts.is_empty() {
+            ProfileParts::ALL
+        } else {
+            parts.into_iter().collect()
+        };
+        let profile = self.client.profile_parts(&btag, parts).await?;
+        let last_updated = profile.value.last_updated;
+        Ok(CachedResponse::from(profile).last_modified(last_updated))
+    }
+
+    #[oai(path = "/overbuff", method = "get")]
+    async fn overbuff(
+        &self,
+        Query(name): Query<String>,
+        Query(number): Query<u64>,
+    ) -> Result<CachedResponse<Arc<Overbuff>>> {
+        let btag = battletag(name, number)?;
+        Ok(self.client.overbuff(&btag).await?.into())
+    }
+
+    /// Stats are taken from competitive PC unless another platform or mode is given.
+    #[oai(path = "/summary", method = "get")]
+    async fn summary(
+        &self,
+        Query(name): Query<String>,
+        Query(number): Query<u64>,
+        Query(platform): Query<Option<Platform>>,
+        Query(mode): Query<Option<Mode>>,
+    ) -> Result<CachedResponse<PlayerSummary>> {
+        let btag = battletag(name, number)?;
+        let platform = platform.unwrap_or(Platform::Pc);
+        let mode = mode.unwrap_or(Mode::Competitive);
+        Ok(self.client.summary(&btag, platform, mode).await?.into())
+    }
+
+    #[oai(path = "/players:batch", method = "post")]
+    async fn batch(
+        &self,
+        Json(request): Json<BatchRequest>,
+    ) -> Result<CombinedResponse<BatchResponse>> {
+        let max = self.limits.batch_size;
+        if request.battletags.len() > max {
+            let message = format!("At most {max} battletags per batch");
+            return Err(Error::bad_request(message));
+        }
+        let concurrency = self.limits.batch_concurrency;
+        Ok(self.client.batch(&request, concurrency).await.into())
+    }
+
+    #[oai(path = "/assets", method = "get")]
+    async fn assets(
+        &self,
+        #[oai(name = "If-None-Match")] Header(if_none_match): Header<Option<String>>,
+    ) -> StaticResponse<&HashMap<Id, Asset>> {
+        let if_none_match = if_none_match.as_deref();
+        StaticResponse::new(self.client.assets(), &self.assets_etag, if_none_match)
+    }
+
+    #[oai(path = "/heroes", method = "get")]
+    async fn heroes(
+        &self,
+        #[oai(name = "If-None-Match")] Header(if_none_match): Header<Option<String>>,
+    ) -> StaticResponse<&[Hero]> {
+        let if_none_match = if_none_match.as_deref();
+        StaticResponse::new(self.client.heroes(), &self.heroes_etag, if_none_match)
+    }
+
+    #[oai(path = "/metrics", method = "get")]
+    async fn metrics(&self) -> Json<CacheMetrics> {
+        Json(self.client.metrics())
+    }
+}
+
+/// The battletag `name#number`, if the name is valid.
+fn battletag(name: String, number: u64) -> Result<Battletag> {
+    let btag = Battletag::new(name, number);
+    if btag.is_valid() {
+        Ok(btag)
+    } else {
+        Err(Error::invalid_battletag(btag))
+    }
+}
+
+/// Periodically writes new cache entries to disk, and once more on shutdown.
+async fn flush_cache(client: Weak<CachedClient>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    loop {
+        let stop = tokio::select! {
+            _ = interval.tick() => false,
+            _ = &mut shutdown => true,
+        };
+        let Some(client) = client.upgrade() else {
+            return;
+        };
+        client.flush();
+        if stop {
+            return;
+        }
+    }
+}
+
+/// Completes on Ctrl+C, or on SIGTERM as sent by container runtimes and service managers.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(error) = tokio::signal::ctrl_c().await {
+            tracing::error!(%error, "Could not listen for Ctrl+C");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(error) => {
+                tracing::error!(%error, "Could not listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        () = ctrl_c => {}
+        () = terminate => {}
+    }
+}
+
+/// Periodically runs the canary against recorded and fresh pages and logs any drift.
+async fn watch_canary(client: Weak<CachedClient>, fixtures: PathBuf, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+    loop {
+        interval.tick().await;
+        let Some(client) = client.upgrade() else {
+            return;
+        };
+        let canary = match Canary::load(&fixtures) {
+            Ok(canary) => canary,
+            Err(error) => {
+                tracing::error!(%error, "Could not load canary");
+                return;
+            }
+        };
+        let report = client.canary(&canary, true).await;
+        if report.drifted {
+            tracing::warn!(?report, "Scraper drift detected");
+        }
+    }
+}
+
+/// Loads the config and sets up the API, failing with a readable error on invalid settings.
+pub async fn startup(cli: &Cli) -> StartupResult<(impl Endpoint, Arc<CachedClient>)> {
+    let config = Config::load(cli.config.clone(), |config| cli.apply(config))?;
+    setup(cli, &config).await
+}
+
+/// Sets up the API with `config` instead of the config file, serves it on a free local port in
+/// the background and returns its address. `cli.bind` is ignored.
+pub async fn spawn(cli: &Cli, config: &Config) -> StartupResult<SocketAddr> {
+    let (app, _) = setup(cli, config).await?;
+    let acceptor = TcpListener::bind("127.0.0.1:0").into_acceptor().await?;
+    let addr = acceptor
+        .local_addr()
+        .first()
+        .and_then(|addr| addr.as_socket_addr().copied())
+        .expect("TCP listener has a socket address");
+    tokio::spawn(async move {
+        if let Err(error) = Server::new_with_acceptor(acceptor).run(app).await {
+            tracing::error!(%error, "Server failed");
+        }
+    });
+    Ok(addr)
+}
+
+async fn setup(cli: &Cli, config: &Config) -> StartupResult<(impl Endpoint, Arc<CachedClient>)> {
+    let api = Api::new(cli, config)
+        .await
+        .map_err(|error| format!("Could not set up the client: {error}"))?;
+    let client = api.client.clone();
+    Ok((app(api, cli, config), client))
+}
+
+/// The API with its documentation and the lookup frontend.
+fn app(api: Api, cli: &Cli, config: &Config) -> impl Endpoint {
+    let v2 = V2Api::new(api.client.clone());
+    let admin = AdminApi::new(api.client.clone(), cli, config);
+    if let Some(secs) = cli.canary_interval {
+        let canary = watch_canary(
+            Arc::downgrade(&api.client),
+            cli.fixtures.clone(),
+            Duration::from_secs(secs),
+        );
+        tokio::spawn(canary);
+    }
+    let api_service = OpenApiService::new((api, v2, admin), "Sombra", env!("CARGO_PKG_VERSION"))
+        .contact(
+            ContactObject::new()
+                .url("https://atilo.sh")
+                .name("by atilo"),
+        )
+        .url_prefix("/api")
+        .external_document("https://github.com/Atilogit/sombra")
+        .license("MIT License");
+    let ui = api_service.swagger_ui();
+    let spec_json = api_service.spec_endpoint();
+    let spec_yaml = api_service.spec_endpoint_yaml();
+    let limiter = Arc::new(RateLimiter::new(config.rate_limit.clone()));
+    let api_service = api_service
+        .around(move |ep, req| {
+            let limiter = limiter.clone();
+            async move { limiter.limit(ep, req).await }
+        })
+        .around(conditional::conditional);
+    Route::new()
+        .nest("/api", api_service)
+        .nest("/docs", ui)
+        .nest("/spec.json", spec_json)
+        .nest("/spec.yaml", spec_yaml)
+        .nest(
+            "/",
+            StaticFilesEndpoint::new(&cli.static_dir)
+                .fallback_to_index()
+                .index_file("index.html"),
+        )
+        .with(middleware::Compression::new())
+        .with(middleware::Tracing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn sigterm_shuts_down() {
+        let shutdown = tokio::spawn(shutdown_signal());
+        // lets the single threaded runtime poll the signal handlers into place
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!shutdown.is_finished());
+        let killed = std::process::Command::new("kill")
+            .args(["-TERM", &std::process::id().to_string()])
+            .status()
+            .unwrap();
+        assert!(killed.success());
+        tokio::time::timeout(Duration::from_secs(5), shutdown)
+            .await
+            .expect("SIGTERM did not shut down")
+            .unwrap();
+    }
+}
@@ -0,0 +1,451 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use cached::{Cached, SizedCache};
+use parking_lot::Mutex;
+use poem::{http::header::HeaderName, Endpoint, IntoResponse, Request, Response};
+
+use crate::{
+    config::{BucketConfig, RateLimitConfig},
+    error::Error,
+    response::UPSTREAM_FETCHES,
+};
+
+/// Number of buckets kept. The least recently used one is dropped for a new one, which starts
+/// out full just like the dropped one most likely was.
+const MAX_BUCKETS: usize = 10_000;
+
+/// Limits the requests of every client with token buckets, taking the `hit_cost` of the endpoint
+/// before a request and the rest of its `fetch_cost` after it for every upstream fetch reported
+/// in `X-Upstream-Fetches`. Every response reports the bucket in `RateLimit-*` headers.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    key_header: HeaderName,
+    /// Name and factor of every API key
+    keys: HashMap<String, (String, f64)>,
+    buckets: Mutex<SizedCache<BucketKey, Bucket>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ClientId {
+    /// Name of the API key
+    Key(String),
+    Ip(IpAddr),
+    /// Connections without an IP address share a bucket
+    Unknown,
+}
+
+/// Client and the endpoint path of the bucket, `None` for the default one.
+type BucketKey = (ClientId, Option<String>);
+
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    capacity: f64,
+    refill_per_second: f64,
+    hit_cost: f64,
+    fetch_cost: f64,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    limits: Limits,
+}
+
+/// State of a bucket after taking tokens from it.
+#[derive(Debug)]
+struct Quota {
+    allowed: bool,
+    limits: Limits,
+    tokens: f64,
+    /// Tokens that were asked for
+    cost: f64,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        let key_header = HeaderName::try_from(config.key_header.as_str())
+            .expect("key header is validated with the config");
+        let keys = config
+            .keys
+            .iter()
+            .map(|(name, key)| (key.key.clone(), (name.clone(), key.factor)))
+            .collect();
+        Self {
+            config,
+            key_header,
+            keys,
+            buckets: Mutex::new(SizedCache::with_size(MAX_BUCKETS)),
+        }
+    }
+
+    pub async fn limit<E: Endpoint>(&self, ep: Arc<E>, req: Request) -> poem::Result<Response> {
+        if !self.config.enabled {
+            return Ok(ep.call(req).await?.into_response());
+        }
+        let Some((client, factor)) = self.client(&req) else {
+            return Ok(Error::invalid_api_key().into_response());
+        };
+        let (endpoint, bucket) = self.endpoint(req.uri().path());
+        let limits = Limits::new(bucket, factor);
+        let key = (client, endpoint);
+
+        let quota = self.take(&key, limits, limits.hit_cost, false);
+        if !quota.allowed {
+            let mut resp = Error::too_many_requests(secs(quota.retry_after())).into_response();
+            quota.write_headers(&mut resp);
+            return Ok(resp);
+        }
+        let mut resp = ep.call(req).await?.into_response();
+        let fetches: u32 = resp
+            .headers()
+            .get(UPSTREAM_FETCHES)
+            .and_then(|v| v.to_str().ok()?.parse().ok())
+            .unwrap_or(0);
+        let quota = if fetches > 0 {
+            let cost = (limits.fetch_cost - limits.hit_cost) * f64::from(fetches);
+            self.take(&key, limits, cost, true)
+        } else {
+            quota
+        };
+        quota.write_headers(&mut resp);
+        Ok(resp)
+    }
+
+    /// The client sending `req` and the factor of its limits. `None` if its API key is unknown.
+    fn client(&self, req: &Request) -> Option<(ClientId, f64)> {
+        if let Some(key) = req.headers().get(&self.key_header) {
+            let (name, factor) = key.to_str().ok().and_then(|key| self.keys.get(key))?;
+            return Some((ClientId::Key(name.clone()), *factor));
+        }
+        let ip = self
+            .forwarded_for(req)
+            .or_else(|| Some(req.remote_addr().as_socket_addr()?.ip()));
+        Some((ip.map_or(ClientId::Unknown, ClientId::Ip), 1.0))
+    }
+
+    /// The address `X-Forwarded-For` was received from by the outermost of the trusted proxies,
+    /// each of which appends the address it received the request from. Entries before it may
+    /// be made up by the client.
+    fn forwarded_for(&self, req: &Request) -> Option<IpAddr> {
+        let hops = self.config.trusted_proxies;
+        if hops == 0 {
+            return None;
+        }
+        let addrs: Vec<_> = req
+            .headers()
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+        addrs.get(addrs.len().checked_sub(hops)?)?.parse().ok()
+    }
+
+    /// The configured endpoint matching `path` and its bucket. Exact paths take precedence over
+    /// prefixes, longer prefixes over shorter ones.
+    fn endpoint(&self, path: &str) -> (Option<String>, &BucketConfig) {
+        let endpoints = &self.config.endpoints;
+        let exact = endpoints.get_key_value(path);
+        let prefix = || {
+            endpoints
+                .iter()
+                .filter(|(pattern, _)| {
+                    pattern
+                        .strip_suffix('*')
+                        .is_some_and(|prefix| path.starts_with(prefix))
+                })
+                .max_by_key(|(pattern, _)| pattern.len())
+        };
+        match exact.or_else(prefix) {
+            Some((pattern, bucket)) => (Some(pattern.clone()), bucket),
+            None => (None, &self.config.default),
+        }
+    }
+
+    /// Refills the bucket of `key` and takes `cost` tokens from it if it holds enough, or
+    /// regardless of that if `force` is set.
+    fn take(&self, key: &BucketKey, limits: Limits, cost: f64, force: bool) -> Quota {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock();
+        let bucket = buckets.cache_get_or_set_with(key.clone(), || Bucket {
+            tokens: limits.capacity,
+            updated: now,
+            limits,
+        });
+        bucket.tokens = bucket.refilled(now);
+        bucket.updated = now;
+        bucket.limits = limits;
+        let allowed = force || bucket.tokens >= cost;
+        if allowed {
+            bucket.tokens -= cost;
+        }
+        Quota {
+            allowed,
+            limits,
+            tokens: bucket.tokens,
+            cost,
+        }
+    }
+}
+
+impl Limits {
+    fn new(bucket: &BucketConfig, factor: f64) -> Self {
+        Self {
+            capacity: f64::from(bucket.capacity) * factor,
+            refill_per_second: bucket.refill_per_second * factor,
+            hit_cost: f64::from(bucket.hit_cost),
+            fetch_cost: f64::from(bucket.fetch_cost),
+        }
+    }
+
+    /// Time until `tokens` have been added.
+    fn refill_time(&self, tokens: f64) -> Duration {
+        Duration::from_secs_f64((tokens / self.refill_per_second).max(0.0))
+    }
+}
+
+impl Bucket {
+    fn refilled(&self, now: Instant) -> f64 {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        (self.tokens + elapsed * self.limits.refill_per_second).min(self.limits.capacity)
+    }
+}
+
+impl Quota {
+    /// Time until the tokens that were asked for are available.
+    fn retry_after(&self) -> Duration {
+        self.limits.refill_time(self.cost - self.tokens)
+    }
+
+    /// Adds `RateLimit-Limit`, `RateLimit-Remaining`, `RateLimit-Reset` in seconds until the
+    /// bucket is full again, and `RateLimit-Policy` with the time to refill an empty bucket.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn write_headers(&self, resp: &mut Response) {
+        let limit = self.limits.capacity.floor() as u64;
+        let remaining = self.tokens.max(0.0).floor() as u64;
+        let reset = secs(self.limits.refill_time(self.limits.capacity - self.tokens));
+        let window = secs(self.limits.refill_time(self.limits.capacity));
+        let headers = [
+            ("ratelimit-limit", limit.to_string()),
+            ("ratelimit-remaining", remaining.to_string()),
+            ("ratelimit-reset", reset.to_string()),
+            ("ratelimit-policy", format!("{limit};w={window}")),
+        ];
+        for (name, value) in headers {
+            if let Ok(value) = value.parse() {
+                resp.headers_mut().insert(name, value);
+            }
+        }
+    }
+}
+
+/// Whole seconds, rounded up.
+fn secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use poem::{endpoint::make_sync, http::StatusCode, test::TestClient, EndpointExt};
+
+    use super::*;
+    use crate::config::ApiKeyConfig;
+
+    const BUCKET: BucketConfig = BucketConfig {
+        capacity: 10,
+        refill_per_second: 2.0,
+        hit_cost: 1,
+        fetch_cost: 5,
+    };
+
+    fn limiter(config: RateLimitConfig) -> Arc<RateLimiter> {
+        Arc::new(RateLimiter::new(RateLimitConfig {
+            default: BUCKET,
+            ..config
+        }))
+    }
+
+    /// Answers with the number of upstream fetches the request asks for, if any.
+    fn client(limiter: Arc<RateLimiter>) -> TestClient<impl Endpoint> {
+        let ep = make_sync(|req| {
+            let mut resp = Response::builder();
+            if let Some(fetches) = req.header("x-test-fetches") {
+                resp = resp.header(UPSTREAM_FETCHES, fetches);
+            }
+            resp.finish()
+        });
+        TestClient::new(ep.around(move |ep, req| {
+            let limiter = limiter.clone();
+            async move { limiter.limit(ep, req).await }
+        }))
+    }
+
+    #[test]
+    fn buckets_refill_up_to_their_capacity() {
+        let limits = Limits::new(&BUCKET, 1.0);
+        let updated = Instant::now();
+        let bucket = Bucket {
+            tokens: 2.0,
+            updated,
+            limits,
+        };
+        let refilled = bucket.refilled(updated + Duration::from_millis(1500));
+        assert!((refilled - 5.0).abs() < 1e-9);
+        let refilled = bucket.refilled(updated + Duration::from_secs(60));
+        assert!((refilled - 10.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn key_factors_scale_capacity_and_refill_but_not_costs() {
+        let limits = Limits::new(&BUCKET, 2.5);
+        assert!((limits.capacity - 25.0).abs() < f64::EPSILON);
+        assert!((limits.refill_per_second - 5.0).abs() < f64::EPSILON);
+        assert!((limits.hit_cost - 1.0).abs() < f64::EPSILON);
+        assert!((limits.fetch_cost - 5.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn quotas_report_when_tokens_are_available() {
+        let quota = Quota {
+            allowed: false,
+            limits: Limits::new(&BUCKET, 1.0),
+            tokens: 1.5,
+            cost: 5.0,
+        };
+        assert_eq!(quota.retry_after(), Duration::from_millis(1750));
+        assert_eq!(secs(quota.retry_after()), 2);
+
+        let mut resp = Response::default();
+        quota.write_headers(&mut resp);
+        let header = |name| resp.headers()[name].to_str().unwrap();
+        assert_eq!(header("ratelimit-limit"), "10");
+        assert_eq!(header("ratelimit-remaining"), "1");
+        // 8.5 tokens are missing
+        assert_eq!(header("ratelimit-reset"), "5");
+        assert_eq!(header("ratelimit-policy"), "10;w=5");
+    }
+
+    #[tokio::test]
+    async fn every_fetch_takes_the_fetch_cost() {
+        let client = client(limiter(RateLimitConfig::default()));
+        let resp = client.get("/").header("x-test-fetches", "0").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_header("ratelimit-remaining", "9");
+
+        let resp = client.get("/").header("x-test-fetches", "1").send().await;
+        resp.assert_header("ratelimit-remaining", "4");
+
+        // e.g. errors and the admin API
+        let resp = client.get("/").send().await;
+        resp.assert_header("ratelimit-remaining", "3");
+
+        // one request may fetch several players, overdrawing the bucket
+        let resp = client.get("/").header("x-test-fetches", "3").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_header("ratelimit-remaining", "0");
+        let resp = client.get("/").send().await;
+        resp.assert_status(StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn empty_buckets_are_rejected() {
+        let client = client(limiter(RateLimitConfig::default()));
+        for _ in 0..2 {
+            let resp = client.get("/").header("x-test-fetches", "1").send().await;
+            resp.assert_status_is_ok();
+        }
+        let resp = client.get("/").send().await;
+        resp.assert_status(StatusCode::TOO_MANY_REQUESTS);
+        resp.assert_header("retry-after", "1");
+        resp.assert_header("ratelimit-remaining", "0");
+    }
+
+    #[tokio::test]
+    async fn disabled_limits_add_no_headers() {
+        let config = RateLimitConfig {
+            enabled: false,
+            ..RateLimitConfig::default()
+        };
+        let resp = client(limiter(config)).get("/").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_header_is_not_exist("ratelimit-remaining");
+    }
+
+    #[tokio::test]
+    async fn api_keys_have_their_own_buckets() {
+        let key = ApiKeyConfig {
+            key: "secret".to_owned(),
+            factor: 2.0,
+        };
+        let config = RateLimitConfig {
+            keys: BTreeMap::from([("alice".to_owned(), key)]),
+            ..RateLimitConfig::default()
+        };
+        let client = client(limiter(config));
+        let resp = client.get("/").header("x-api-key", "secret").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_header("ratelimit-remaining", "19");
+
+        let resp = client.get("/").send().await;
+        resp.assert_header("ratelimit-remaining", "9");
+
+        let resp = client.get("/").header("x-api-key", "guess").send().await;
+        resp.assert_status(StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn clients_are_taken_from_trusted_forwarding_hops() {
+        let req = Request::builder()
+            .header("x-forwarded-for", "1.1.1.1, 2.2.2.2")
+            .header("x-forwarded-for", "3.3.3.3")
+            .finish();
+        let forwarded_for = |trusted_proxies| {
+            limiter(RateLimitConfig {
+                trusted_proxies,
+                ..RateLimitConfig::default()
+            })
+            .forwarded_for(&req)
+        };
+        assert_eq!(forwarded_for(0), None);
+        assert_eq!(forwarded_for(1), Some([3, 3, 3, 3].into()));
+        assert_eq!(forwarded_for(2), Some([2, 2, 2, 2].into()));
+        assert_eq!(forwarded_for(3), Some([1, 1, 1, 1].into()));
+        assert_eq!(forwarded_for(4), None);
+
+        let req = Request::builder()
+            .header("x-forwarded-for", "unknown")
+            .finish();
+        let limiter = limiter(RateLimitConfig {
+            trusted_proxies: 1,
+            ..RateLimitConfig::default()
+        });
+        assert_eq!(limiter.forwarded_for(&req), None);
+        assert_eq!(limiter.client(&req), Some((ClientId::Unknown, 1.0)));
+    }
+
+    #[test]
+    fn least_recently_used_buckets_are_dropped() {
+        let limiter = limiter(RateLimitConfig::default());
+        let limits = Limits::new(&BUCKET, 1.0);
+        let key = |i: usize| (ClientId::Ip(IpAddr::from((i as u128).to_be_bytes())), None);
+        for i in 0..=MAX_BUCKETS {
+            limiter.take(&key(i % MAX_BUCKETS), limits, 1.0, false);
+        }
+        limiter.take(&key(MAX_BUCKETS), limits, 1.0, false);
+
+        let mut buckets = limiter.buckets.lock();
+        assert_eq!(buckets.cache_size(), MAX_BUCKETS);
+        assert!(buckets.cache_get(&key(0)).is_some());
+        assert!(buckets.cache_get(&key(1)).is_none());
+        assert!(buckets.cache_get(&key(MAX_BUCKETS)).is_some());
+    }
+}
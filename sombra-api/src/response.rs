@@ -0,0 +1,131 @@
+use chrono::{DateTime, Utc};
+use poem_openapi::{payload::Json, types::ToJSON, ApiResponse};
+use sombra::Cached;
+
+use crate::conditional::{self, http_date};
+
+pub const CACHE_HIT: &str = "HIT";
+pub const CACHE_MISS: &str = "MISS";
+/// Header with the number of upstream fetches a response took, which the rate limiter charges
+pub const UPSTREAM_FETCHES: &str = "x-upstream-fetches";
+
+/// Static data is revalidated on every use, which only costs a `304` while it is unchanged.
+const STATIC_CACHE_CONTROL: &str = "public, no-cache";
+
+#[derive(ApiResponse)]
+pub enum CachedResponse<T: ToJSON> {
+    /// `Age` is the number of seconds since the value was fetched upstream.
+    /// `X-Cache-Stale` is set once the value has outlived its soft TTL.
+    /// `X-Cache` is `MISS` if the value was fetched upstream for this request, `HIT` otherwise.
+    /// `X-Upstream-Fetches` is the number of upstream fetches made for this request.
+    /// `Cache-Control` allows caching for the rest of its soft TTL.
+    /// Requests with a current `If-None-Match` or `If-Modified-Since` get `304 Not Modified`.
+    #[oai(status = 200)]
+    Ok(
+        Json<T>,
+        #[oai(header = "Age")] u64,
+        #[oai(header = "X-Cache-Stale")] bool,
+        #[oai(header = "X-Cache")] String,
+        #[oai(header = "X-Upstream-Fetches")] u32,
+        #[oai(header = "Cache-Control")] String,
+        #[oai(header = "Last-Modified")] Option<String>,
+    ),
+}
+
+/// Response combining cached values that is not cached itself, e.g. that of a `POST`.
+#[derive(ApiResponse)]
+pub enum CombinedResponse<T: ToJSON> {
+    /// `X-Cache` is `MISS` if any of the values was fetched upstream for this request, `HIT`
+    /// otherwise. `X-Upstream-Fetches` is the number of upstream fetches made for it.
+    #[oai(status = 200)]
+    Ok(
+        Json<T>,
+        #[oai(header = "X-Cache")] String,
+        #[oai(header = "X-Upstream-Fetches")] u32,
+    ),
+}
+
+/// Response for data that only changes when the API restarts, with an `ETag` computed once.
+#[derive(ApiResponse)]
+pub enum StaticResponse<T: ToJSON> {
+    #[oai(status = 200)]
+    Ok(
+        Json<T>,
+        #[oai(header = "ETag")] String,
+        #[oai(header = "Cache-Control")] String,
+        #[oai(header = "X-Cache")] String,
+    ),
+    /// The `If-None-Match` of the request contains the current `ETag`
+    #[oai(status = 304)]
+    NotModified(
+        #[oai(header = "ETag")] String,
+        #[oai(header = "Cache-Control")] String,
+    ),
+}
+
+impl<T: ToJSON> CachedResponse<T> {
+    /// Sets `Last-Modified`, e.g. to when a profile was last updated upstream.
+    #[must_use]
+    pub fn last_modified(self, date: DateTime<Utc>) -> Self {
+        match self {
+            Self::Ok(json, age, stale, cache, fetches, cache_control, _) => Self::Ok(
+                json,
+                age,
+                stale,
+                cache,
+                fetches,
+                cache_control,
+                Some(http_date(date)),
+            ),
+        }
+    }
+}
+
+impl<T: ToJSON> From<Cached<T>> for CachedResponse<T> {
+    fn from(cached: Cached<T>) -> Self {
+        let cache = x_cache(&cached);
+        let cache_control = format!("public, max-age={}", cached.max_age.as_secs());
+        Self::Ok(
+            Json(cached.value),
+            cached.age.as_secs(),
+            cached.stale,
+            cache,
+            cached.fetches,
+            cache_control,
+            None,
+        )
+    }
+}
+
+impl<T: ToJSON> From<Cached<T>> for CombinedResponse<T> {
+    fn from(cached: Cached<T>) -> Self {
+        let cache = x_cache(&cached);
+        Self::Ok(Json(cached.value), cache, cached.fetches)
+    }
+}
+
+impl<T: ToJSON> StaticResponse<T> {
+    /// `etag` is the tag of `value`. Only the tag is sent if it is in `if_none_match`.
+    pub fn new(value: T, etag: &str, if_none_match: Option<&str>) -> Self {
+        let cache_control = STATIC_CACHE_CONTROL.to_owned();
+        if if_none_match.is_some_and(|tags| conditional::matches(tags, etag)) {
+            Self::NotModified(etag.to_owned(), cache_control)
+        } else {
+            Self::Ok(
+                Json(value),
+                etag.to_owned(),
+                cache_control,
+                CACHE_HIT.to_owned(),
+            )
+        }
+    }
+}
+
+fn x_cache<T>(cached: &Cached<T>) -> String {
+    let cache = if cached.fetched() {
+        CACHE_MISS
+    } else {
+        CACHE_HIT
+    };
+    cache.to_owned()
+}
@@ -0,0 +1,135 @@
+use std::{collections::HashMap, sync::Arc};
+
+use poem_openapi::{param::Path, OpenApi};
+use sombra::{
+    Battletag, CachedClient, HeroStats, Mode, Platform, PlayerHeroStats, PlayerProfileReduced,
+    ProfileParts, ReconciledRanks,
+};
+
+use crate::{
+    error::{Error, Result},
+    response::CachedResponse,
+    ApiTags,
+};
+
+/// Resource-oriented routes. The `battletag` path segment is either `Name-1234` or the
+/// percent-encoded `Name#1234`.
+pub struct V2Api {
+    client: Arc<CachedClient>,
+}
+
+impl V2Api {
+    pub fn new(client: Arc<CachedClient>) -> Self {
+        Self { client }
+    }
+}
+
+#[OpenApi(prefix_path = "/v2", tag = "ApiTags::V2")]
+impl V2Api {
+    #[oai(path = "/players/:battletag", method = "get")]
+    async fn player(
+        &self,
+        Path(battletag): Path<String>,
+    ) -> Result<CachedResponse<PlayerProfileReduced>> {
+        let btag = parse_battletag(&battletag)?;
+        let profile = self.client.profile(&btag).await?;
+        let last_updated = profile.value.last_updated;
+        Ok(CachedResponse::from(profile).last_modified(last_updated))
+    }
+
+    #[oai(path = "/players/:battletag/ranks", method = "get")]
+    async fn ranks(
+        &self,
+        Path(battletag): Path<String>,
+    ) -> Result<CachedResponse<ReconciledRanks>> {
+        let btag = parse_battletag(&battletag)?;
+        Ok(self.client.ranks(&btag).await?.into())
+    }
+
+    #[oai(path = "/players/:battletag/stats/:platform/:mode", method = "get")]
+    async fn stats(
+        &self,
+        Path(battletag): Path<String>,
+        Path(platform): Path<Platform>,
+        Path(mode): Path<Mode>,
+    ) -> Result<CachedResponse<HashMap<String, HeroStats>>> {
+        let btag = parse_battletag(&battletag)?;
+        let parts = ProfileParts::table(platform, mode);
+        let profile = self.client.profile_parts(&btag, parts).await?;
+        let stats = profile
+            .value
+            .stats(platform, mode)
+            .ok_or_else(Error::private_profile)?
+            .clone();
+        let last_updated = profile.value.last_updated;
+        Ok(CachedResponse::from(profile.map(|_| stats)).last_modified(last_updated))
+    }
+
+    /// `hero` is matched ignoring case, punctuation and accents, e.g. `soldier-76`.
+    #[oai(path = "/players/:battletag/heroes/:hero", method = "get")]
+    async fn hero(
+        &self,
+        Path(battletag): Path<String>,
+        Path(hero): Path<String>,
+    ) -> Result<CachedResponse<PlayerHeroStats>> {
+        let btag = parse_battletag(&battletag)?;
+        let profile = self
+            .client
+            .profile_parts(&btag, ProfileParts::STATS)
+            .await?;
+        if profile.value.visibility.is_private() {
+            return Err(Error::private_profile());
+        }
+        let stats = profile
+            .value
+            .hero_stats(&hero)
+            .ok_or_else(|| Error::not_found(format!("No stats for hero {hero}")))?;
+        let last_updated = profile.value.last_updated;
+        Ok(CachedResponse::from(profile.map(|_| stats)).last_modified(last_updated))
+    }
+}
+
+fn parse_battletag(segment: &str) -> Result<Battletag> {
+    let btag: Battletag = percent_encoding::percent_decode_str(segment)
+        .decode_utf8()
+        .map_err(|_| Error::invalid_battletag(segment))?
+        .parse()
+        .map_err(|()| Error::invalid_battletag(segment))?;
+    if btag.is_valid() {
+        Ok(btag)
+    } else {
+        Err(Error::invalid_battletag(btag))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn battletags_are_read_from_path_segments() {
+        let btag = Battletag::new("Player", 1234);
+        assert_eq!(parse_battletag("Player-1234").unwrap(), btag);
+        assert_eq!(parse_battletag("Player%231234").unwrap(), btag);
+        assert_eq!(
+            parse_battletag("Pl%C3%A2yer-1").unwrap(),
+            Battletag::new("Plâyer", 1)
+        );
+    }
+
+    #[test]
+    fn invalid_battletags_are_bad_requests() {
+        for segment in [
+            "Player",
+            "Player-",
+            "%FF-1234",
+            "Some%20Name-1234",
+            "Some-Name-1",
+        ] {
+            assert!(
+                matches!(parse_battletag(segment), Err(Error::BadRequest(_))),
+                "{segment}"
+            );
+        }
+    }
+}
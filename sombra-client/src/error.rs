@@ -1,4 +1,5 @@
-use reqwest::StatusCode;
+use reqwest::{Response, StatusCode};
+use sombra_types::ApiError;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -11,6 +12,9 @@ pub enum Error {
     Deserializer(#[from] serde_json::Error),
     #[error("HTTP error: {0}")]
     Http(StatusCode),
+    /// Error response of the API
+    #[error("API error {0}: {}", .1.message)]
+    Api(StatusCode, ApiError),
 }
 
 impl Error {
@@ -26,4 +30,18 @@ impl Error {
             Err(Self::Http(code))
         }
     }
+
+    /// Passes a successful response through. Failed ones become [`Error::Api`] if they carry an
+    /// error body and [`Error::Http`] otherwise.
+    pub async fn check_response(response: Response) -> Result<Response> {
+        let status = response.status();
+        if status == StatusCode::OK {
+            return Ok(response);
+        }
+        let body = response.text().await.unwrap_or_default();
+        Err(match serde_json::from_str(&body) {
+            Ok(error) => Self::Api(status, error),
+            Err(_) => Self::Http(status),
+        })
+    }
 }
@@ -23,7 +23,7 @@ impl Client {
     pub async fn search(&self, name: &str) -> Result<Vec<FoundPlayer>> {
         let url = format!("{}/api/v1/search", self.url);
         let response = self.client.get(url).query(&[("name", name)]).send().await?;
-        Error::result_from_status(response.status(), None)?;
+        let response = Error::check_response(response).await?;
         Ok(serde_json::from_str(&response.text().await?)?)
     }
 
@@ -35,7 +35,7 @@ impl Client {
             .query(&[("name", &btag.name), ("number", &btag.number.to_string())])
             .send()
             .await?;
-        Error::result_from_status(response.status(), None)?;
+        let response = Error::check_response(response).await?;
         Ok(serde_json::from_str(&response.text().await?)?)
     }
 
@@ -47,7 +47,41 @@ impl Client {
             .query(&[("name", &btag.name), ("number", &btag.number.to_string())])
             .send()
             .await?;
-        Error::result_from_status(response.status(), None)?;
+        let response = Error::check_response(response).await?;
+        Ok(serde_json::from_str(&response.text().await?)?)
+    }
+
+    /// Like [`profile_full`](Self::profile_full), but only the selected parts are filled in.
+    pub async fn profile_parts(
+        &self,
+        btag: &Battletag,
+        parts: ProfileParts,
+    ) -> Result<PlayerProfile> {
+        let url = format!("{}/api/v1/profile_full", self.url);
+        let mut query = vec![
+            ("name", btag.name.clone()),
+            ("number", btag.number.to_string()),
+        ];
+        query.extend(
+            parts
+                .parts()
+                .iter()
+                .map(|p| ("parts", p.as_str().to_owned())),
+        );
+        let response = self.client.get(url).query(&query).send().await?;
+        let response = Error::check_response(response).await?;
+        Ok(serde_json::from_str(&response.text().await?)?)
+    }
+
+    pub async fn summary(&self, btag: &Battletag) -> Result<PlayerSummary> {
+        let url = format!("{}/api/v1/summary", self.url);
+        let response = self
+            .client
+            .get(url)
+            .query(&[("name", &btag.name), ("number", &btag.number.to_string())])
+            .send()
+            .await?;
+        let response = Error::check_response(response).await?;
         Ok(serde_json::from_str(&response.text().await?)?)
     }
 
@@ -59,30 +93,74 @@ impl Client {
             .query(&[("name", &btag.name), ("number", &btag.number.to_string())])
             .send()
             .await?;
-        Error::result_from_status(response.status(), None)?;
+        let response = Error::check_response(response).await?;
+        Ok(serde_json::from_str(&response.text().await?)?)
+    }
+
+    /// Fetches many players at once. Failures are reported per player instead of failing the
+    /// whole batch.
+    pub async fn batch(&self, request: &BatchRequest) -> Result<BatchResponse> {
+        let url = format!("{}/api/v1/players:batch", self.url);
+        let response = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_string(request)?)
+            .send()
+            .await?;
+        let response = Error::check_response(response).await?;
         Ok(serde_json::from_str(&response.text().await?)?)
     }
 
     pub async fn heroes(&self) -> Result<Vec<Hero>> {
         let url = format!("{}/api/v1/heroes", self.url);
         let response = self.client.get(url).send().await?;
-        Error::result_from_status(response.status(), None)?;
+        let response = Error::check_response(response).await?;
         Ok(serde_json::from_str(&response.text().await?)?)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use sombra_api::{Cli, Config};
+    use sombra_mock::Mock;
+
     use super::*;
 
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../sombra-mock/fixtures");
+
+    /// A client of an API scraping a mock server, both running in-process.
+    async fn client() -> Client {
+        let upstream = Mock::load(FIXTURES).unwrap().spawn().await.unwrap();
+        let mut config = Config::default();
+        config.upstream.blizzard = upstream.blizzard;
+        config.upstream.overbuff = upstream.overbuff;
+        let addr = sombra_api::spawn(&Cli::from_env(), &config).await.unwrap();
+        Client::new(format!("http://{addr}"))
+    }
+
     #[tokio::test]
     async fn test_all() {
-        let client = Client::new("http://localhost:8000");
-        let found = client.search("player").await.unwrap();
+        let client = client().await;
+        let found = client.search("Player").await.unwrap();
         assert!(!found.is_empty());
         client.profile(&found[0].battle_tag).await.unwrap();
         client.profile_full(&found[0].battle_tag).await.unwrap();
         client.overbuff(&found[0].battle_tag).await.unwrap();
         client.heroes().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn upstream_failures_are_api_errors() {
+        let client = client().await;
+        let broken = Battletag::new("Broken", 1234);
+        assert!(matches!(
+            client.profile(&broken).await,
+            Err(Error::Api(status, _)) if status.is_server_error()
+        ));
+        assert!(matches!(
+            client.overbuff(&broken).await,
+            Err(Error::Api(status, _)) if status.is_server_error()
+        ));
+    }
 }
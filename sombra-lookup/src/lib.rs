@@ -29,28 +29,40 @@ pub fn App() -> impl IntoView {
 
     let (btags, set_btags) = create_signal(Vec::<Battletag>::new());
     let (players, set_players) = create_signal(Vec::<Player>::new());
+    let (error, set_error) = create_signal(None::<String>);
 
-    let load_player = create_action(move |btag: &Battletag| {
+    let load_players = create_action(move |btags: &Vec<Battletag>| {
         let client = client.clone();
-        let btag = btag.clone();
+        let btags = btags.clone();
         async move {
             let heroes = client.heroes().await.ok().unwrap_or(Vec::new());
-            let player = Player::fetch(btag, heroes, client).await;
-            if let Some(player) = player {
-                set_players.update(|players| players.push(player));
+            match Player::fetch_all(btags.clone(), heroes, client).await {
+                Ok(loaded) => {
+                    set_error.set(None);
+                    set_players.update(|players| players.extend(loaded));
+                }
+                Err(e) => {
+                    // forget the players so that they are loaded again on the next input
+                    set_btags.update(|v| v.retain(|b| !btags.contains(b)));
+                    set_error.set(Some(format!("Could not load players: {e}")));
+                }
             }
         }
     });
 
     let update_btag_input = move || {
+        let mut new = Vec::new();
         for btag in btag_regex.find_iter(&btag_input.get()) {
             let btag = Battletag::from_str(btag.as_str()).unwrap();
             let exists = btags.with(|v| v.iter().any(|b| b == &btag));
             if !exists {
                 set_btags.update(|v| v.push(btag.clone()));
-                load_player.dispatch(btag);
+                new.push(btag);
             }
         }
+        if !new.is_empty() {
+            load_players.dispatch(new);
+        }
     };
     update_btag_input();
 
@@ -63,6 +75,8 @@ pub fn App() -> impl IntoView {
         <div class="container mx-auto mt-32 mb-16 min-h-screen">
             <textarea class="textarea textarea-bordered w-full mb-8" on:input=on_btag_input prop:value=btag_input rows=10 />
 
+            {move || error.get().map(|e| view! { <div class="alert alert-error mb-8">{e}</div> })}
+
             <div class="grid gap-4 grid-cols-2">
                 <For
                     each=move || players.get()
@@ -4,15 +4,13 @@ use std::{collections::BTreeMap, time::Duration};
 
 use serde_derive::{Deserialize, Serialize};
 use sombra_client::{
-    Battletag, Client, FoundPlayer, Hero, Overbuff, PlayerProfile, Rank, Role, Stat,
+    BatchField, BatchRequest, Battletag, Client, Hero, PlayStats, PlayerSummary, Rank, Role, Stat,
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Player {
     pub btag: Battletag,
-    pub profile: Option<PlayerProfile>,
-    pub overbuff: Option<Overbuff>,
-    pub found: FoundPlayer,
+    pub summary: PlayerSummary,
     pub heroes: Vec<Hero>,
 }
 
@@ -24,52 +22,49 @@ pub struct Stats {
 }
 
 impl Player {
-    pub async fn fetch(btag: Battletag, heroes: Vec<Hero>, client: Client) -> Option<Self> {
-        let found = client
-            .search(&btag.name)
-            .await
-            .ok()
-            .and_then(|v| v.into_iter().find(|p| p.battle_tag == btag))?;
-        let profile = client.profile_full(&btag).await.ok();
-        let fetch_overbuff = profile.is_none()
-            || !found.is_public
-            || profile.as_ref().is_some_and(|p| p.ranks.is_empty());
-        let overbuff = if fetch_overbuff {
-            client.overbuff(&btag).await.ok()
-        } else {
-            None
+    /// Fetches the summaries of a whole lobby in one batch. Players whose summary could not be
+    /// fetched are left out.
+    pub async fn fetch_all(
+        btags: Vec<Battletag>,
+        heroes: Vec<Hero>,
+        client: Client,
+    ) -> sombra_client::Result<Vec<Self>> {
+        let request = BatchRequest {
+            battletags: btags.clone(),
+            fields: vec![BatchField::Summary],
+            parts: Vec::new(),
         };
-        Some(Self {
-            btag,
-            profile,
-            overbuff,
-            found,
-            heroes,
-        })
+        let mut players = client.batch(&request).await?.players;
+        Ok(btags
+            .into_iter()
+            .filter_map(|btag| {
+                let summary = players.remove(&btag.to_string())?.summary?;
+                Some(Self {
+                    btag,
+                    summary,
+                    heroes: heroes.clone(),
+                })
+            })
+            .collect())
     }
 
     pub fn namecard_url(&self) -> String {
-        self.found
+        self.summary
             .namecard
             .as_ref()
             .map_or(String::new(), std::string::ToString::to_string)
     }
 
     pub fn title(&self) -> String {
-        self.found
-            .title
-            .as_ref()
-            .map_or(String::new(), |t| t["en_US"].clone())
+        self.summary.title.clone().unwrap_or_default()
+    }
+
+    pub const fn is_private(&self) -> bool {
+        self.summary.visibility.is_private()
     }
 
     pub fn ranks(&self) -> Vec<Rank> {
-        let mut ranks = self
-            .overbuff
-            .as_ref()
-            .map(|p| &p.ranks)
-            .or_else(|| self.profile.as_ref().map(|p| &p.ranks))
-            .cloned()
-            .unwrap_or(Vec::new());
+        let mut ranks: Vec<_> = self.summary.ranks.iter().map(|r| r.rank.clone()).collect();
         ranks.sort_by_key(|r| r.role);
         ranks.reverse();
         ranks
@@ -79,90 +74,29 @@ impl Player {
         self.ranks().into_iter().find(|r| r.role == role)
     }
 
-    #[allow(clippy::cast_sign_loss)]
     pub fn role_stats(&self, role: Role) -> Option<Stats> {
-        let all = &self.profile.as_ref()?.competitive_pc;
-        let heroes = self
-            .heroes
-            .iter()
-            .filter(|h| h.role == role)
-            .filter_map(|hero| all.get(&hero.name));
-        let time = heroes
-            .clone()
-            .filter_map(|stats| stats.stats.get("Time Played"))
-            .copied()
-            .sum::<Option<Stat>>()
-            .unwrap_or(Stat::Duration(Duration::ZERO))
-            .as_duration()?;
-        let win = heroes
-            .clone()
-            .filter_map(|stats| stats.stats.get("Games Won"))
-            .copied()
-            .sum::<Option<Stat>>()
-            .unwrap_or(Stat::Number(0.))
-            .as_f64()? as usize;
-        let draw = heroes
-            .clone()
-            .filter_map(|stats| stats.stats.get("Games Tied"))
-            .copied()
-            .sum::<Option<Stat>>()
-            .unwrap_or(Stat::Number(0.))
-            .as_f64()? as usize;
-        let loss = heroes
-            .filter_map(|stats| stats.stats.get("Games Lost"))
-            .copied()
-            .sum::<Option<Stat>>()
-            .unwrap_or(Stat::Number(0.))
-            .as_f64()? as usize;
-        Some(Stats {
-            time,
-            win,
-            draw,
-            loss,
-        })
+        let stats = self.summary.roles.iter().find(|r| r.role == role)?;
+        Some(stats.stats.into())
     }
 
-    #[allow(clippy::cast_sign_loss)]
     pub fn stats(&self) -> Option<Stats> {
-        let all = &self
-            .profile
-            .as_ref()?
-            .competitive_pc
-            .get("ALL HEROES")?
-            .stats;
-        let time = all
-            .get("Time Played")
-            .unwrap_or(&Stat::Duration(Duration::ZERO))
-            .as_duration()?;
-        let win = all.get("Games Won").unwrap_or(&Stat::Number(0.)).as_f64()? as usize;
-        let draw = all
-            .get("Games Tied")
-            .unwrap_or(&Stat::Number(0.))
-            .as_f64()? as usize;
-        let loss = all
-            .get("Games Lost")
-            .unwrap_or(&Stat::Number(0.))
-            .as_f64()? as usize;
-        Some(Stats {
-            time,
-            win,
-            draw,
-            loss,
-        })
+        Some(self.summary.stats?.into())
     }
 
     pub fn hero_stats(&self, hero: &str) -> Option<BTreeMap<String, Stat>> {
-        let visible_stats = ["Time Played", "Win Percentage", "Weapon Accuracy"];
-        Some(
-            self.profile
-                .as_ref()?
-                .competitive_pc
-                .get(hero)?
-                .stats
-                .iter()
-                .filter(|(name, _)| visible_stats.contains(&name.as_str()))
-                .map(|(name, stat)| (name.clone(), *stat))
-                .collect(),
-        )
+        let hero = self.summary.top_heroes.iter().find(|h| h.hero == hero)?;
+        Some(hero.stats.clone().into_iter().collect())
+    }
+}
+
+impl From<PlayStats> for Stats {
+    #[allow(clippy::cast_possible_truncation)]
+    fn from(stats: PlayStats) -> Self {
+        Self {
+            time: Duration::from_secs(stats.time_played),
+            win: stats.wins as usize,
+            draw: stats.ties as usize,
+            loss: stats.losses as usize,
+        }
     }
 }
@@ -42,6 +42,9 @@ impl Player {
                         </div>
 
                         <div class="grid grid-cols-3">
+                            {self.is_private().then(|| view! {
+                                <div class="col-span-3 font-bold">Private Profile</div>
+                            })}
                             {stats.map(|stats|{
                                 view! {
                                     <div class="font-bold">
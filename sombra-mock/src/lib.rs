@@ -0,0 +1,272 @@
+//! Stand-in for the sites `sombra` scrapes, serving recorded responses from a fixture directory
+//! so that everything can run without the internet.
+//!
+//! A request for `/<host>/<path>` is answered with the file `<dir>/<host>/<path>`, or
+//! `<dir>/<host>/<path>/index.html` if the path ends with a slash. Missing files are answered
+//! with 404. Status and headers are taken from the [`Recording`] next to the file if there is
+//! one, which is the layout a [`sombra::Recorder`] writes. Point a client at the mock with
+//! [`Upstream::mirror`].
+//!
+//! Failures are scripted with [`Rule`]s, read from [`SCRIPT_FILE`] in the fixture directory or
+//! added at runtime. Rules can also serve a page saved elsewhere, so that the pages in
+//! `sombra/fixtures` are not copied.
+
+#![allow(clippy::missing_errors_doc)]
+
+mod error;
+
+use std::{
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+use percent_encoding::percent_decode_str;
+use poem::{
+    endpoint::make,
+    http::{header, StatusCode},
+    listener::{Acceptor, Listener, TcpListener},
+    Endpoint, Request, Response, Server,
+};
+use serde_derive::Deserialize;
+
+pub use error::*;
+use sombra::{recording_path, Recording};
+
+pub use sombra::Upstream;
+
+/// File in the fixture directory that the script is loaded from, if it exists.
+pub const SCRIPT_FILE: &str = "script.toml";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Script {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<Rule>,
+}
+
+/// Changes the response to requests for `path`. Only the first matching rule applies.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Rule {
+    /// Request path, or its start if it ends with `*`
+    pub path: String,
+    /// Respond with this status and no body instead of the fixture
+    pub status: Option<u16>,
+    /// Seconds sent in a `Retry-After` header
+    pub retry_after: Option<u64>,
+    /// Wait before responding
+    pub delay_ms: Option<u64>,
+    /// Serve this file, relative to the fixture directory, instead of the one at the path
+    pub file: Option<PathBuf>,
+    /// Serve another recording of the page, `index.<variant>.html` instead of `index.html`
+    pub variant: Option<String>,
+    /// Number of requests the rule applies to before it is removed. Unlimited if unset.
+    pub times: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mock {
+    dir: Arc<PathBuf>,
+    rules: Arc<Mutex<Vec<Rule>>>,
+}
+
+impl Rule {
+    #[must_use]
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub const fn status(mut self, status: u16) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    #[must_use]
+    pub const fn retry_after(mut self, secs: u64) -> Self {
+        self.retry_after = Some(secs);
+        self
+    }
+
+    #[must_use]
+    pub const fn delay_ms(mut self, ms: u64) -> Self {
+        self.delay_ms = Some(ms);
+        self
+    }
+
+    #[must_use]
+    pub fn file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    #[must_use]
+    pub fn variant(mut self, variant: impl Into<String>) -> Self {
+        self.variant = Some(variant.into());
+        self
+    }
+
+    #[must_use]
+    pub const fn times(mut self, times: u32) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        match self.path.strip_suffix('*') {
+            Some(prefix) => path.starts_with(prefix),
+            None => path == self.path,
+        }
+    }
+}
+
+impl Mock {
+    /// Serves `dir` without any rules.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Arc::new(dir.into()),
+            rules: Arc::default(),
+        }
+    }
+
+    /// Serves `dir` with the rules in its [`SCRIPT_FILE`], if there is one.
+    pub fn load(dir: impl Into<PathBuf>) -> Result<Self> {
+        let mock = Self::new(dir);
+        let script = mock.dir.join(SCRIPT_FILE);
+        if script.is_file() {
+            let script: Script = toml::from_str(&fs::read_to_string(script)?)?;
+            *mock.rules.lock() = script.rules;
+        }
+        Ok(mock)
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Adds a rule after all existing ones.
+    pub fn rule(&self, rule: Rule) {
+        self.rules.lock().push(rule);
+    }
+
+    pub fn clear_rules(&self) {
+        self.rules.lock().clear();
+    }
+
+    pub fn endpoint(&self) -> impl Endpoint {
+        let mock = self.clone();
+        make(move |req| {
+            let mock = mock.clone();
+            async move { mock.respond(&req).await }
+        })
+    }
+
+    /// Serves on `addr` until the server fails.
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        Server::new(TcpListener::bind(addr))
+            .run(self.endpoint())
+            .await?;
+        Ok(())
+    }
+
+    /// Serves on a free local port in the background and returns the upstream to scrape.
+    pub async fn spawn(self) -> Result<Upstream> {
+        let acceptor = TcpListener::bind("127.0.0.1:0").into_acceptor().await?;
+        let addr = acceptor
+            .local_addr()
+            .first()
+            .and_then(|addr| addr.as_socket_addr().copied())
+            .expect("TCP listener has a socket address");
+        let endpoint = self.endpoint();
+        tokio::spawn(async move {
+            if let Err(error) = Server::new_with_acceptor(acceptor).run(endpoint).await {
+                tracing::error!(%error, "Mock server failed");
+            }
+        });
+        Ok(Upstream::mirror(&format!("http://{addr}")))
+    }
+
+    async fn respond(&self, req: &Request) -> Response {
+        let path = percent_decode_str(req.uri().path()).decode_utf8_lossy();
+        let rule = self.take_rule(&path);
+        tracing::debug!(%path, ?rule, "Mock request");
+        let rule = rule.unwrap_or_default();
+
+        if let Some(ms) = rule.delay_ms {
+            tokio::time::sleep(Duration::from_millis(ms)).await;
+        }
+        if let Some(status) = rule.status {
+            let mut response = Response::builder()
+                .status(StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR));
+            if let Some(secs) = rule.retry_after {
+                response = response.header(header::RETRY_AFTER, secs);
+            }
+            return response.finish();
+        }
+
+        let file = match &rule.file {
+            Some(file) => self.dir.join(file),
+            None => match recording_path(&self.dir, &path) {
+                Some(file) => file,
+                None => return StatusCode::BAD_REQUEST.into(),
+            },
+        };
+        let file = match &rule.variant {
+            Some(variant) => variant_path(&file, variant),
+            None => file,
+        };
+        let Ok(body) = fs::read(&file) else {
+            return StatusCode::NOT_FOUND.into();
+        };
+        let mut response = Response::builder();
+        match Recording::load(&file) {
+            Ok(Some(recording)) => {
+                response = response
+                    .status(StatusCode::from_u16(recording.status).unwrap_or(StatusCode::OK));
+                for (name, value) in &recording.headers {
+                    response = response.header(name.as_str(), value.as_str());
+                }
+            }
+            Ok(None) if file.extension().is_some_and(|e| e == "html") => {
+                response = response.content_type("text/html; charset=utf-8");
+            }
+            Ok(None) => response = response.content_type("application/json"),
+            Err(error) => {
+                tracing::warn!(?file, %error, "Invalid recording");
+                return StatusCode::INTERNAL_SERVER_ERROR.into();
+            }
+        }
+        response.body(body)
+    }
+
+    /// The first rule matching `path`, which is removed once it has been used up.
+    fn take_rule(&self, path: &str) -> Option<Rule> {
+        let mut rules = self.rules.lock();
+        let i = rules.iter().position(|rule| rule.matches(path))?;
+        let rule = &mut rules[i];
+        match &mut rule.times {
+            Some(times) if *times <= 1 => Some(rules.remove(i)),
+            Some(times) => {
+                *times -= 1;
+                Some(rule.clone())
+            }
+            None => Some(rule.clone()),
+        }
+    }
+}
+
+/// `index.html` becomes `index.<variant>.html`.
+fn variant_path(file: &Path, variant: &str) -> PathBuf {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match file.extension() {
+        Some(extension) => format!("{stem}.{variant}.{}", extension.to_string_lossy()),
+        None => format!("{stem}.{variant}"),
+    };
+    file.with_file_name(name)
+}
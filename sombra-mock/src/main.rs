@@ -0,0 +1,49 @@
+//! Serves the recorded upstream responses in a fixture directory.
+//!
+//! Usage: `sombra-mock [FIXTURES] [--bind ADDR]`
+
+use std::{net::SocketAddr, path::PathBuf, process::ExitCode};
+
+use sombra_mock::{Mock, Upstream};
+
+const DEFAULT_FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+const DEFAULT_ADDR: &str = "127.0.0.1:8001";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut fixtures = PathBuf::from(DEFAULT_FIXTURES);
+    let mut addr = DEFAULT_ADDR.to_owned();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bind" => addr = args.next().unwrap_or(addr),
+            _ => fixtures = arg.into(),
+        }
+    }
+    let addr: SocketAddr = match addr.parse() {
+        Ok(addr) => addr,
+        Err(e) => {
+            eprintln!("Invalid address {addr}: {e}");
+            return ExitCode::from(2);
+        }
+    };
+    let mock = match Mock::load(fixtures) {
+        Ok(mock) => mock,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let upstream = Upstream::mirror(&format!("http://{addr}"));
+    eprintln!("Serving {}", mock.dir().display());
+    eprintln!("Blizzard: {}", upstream.blizzard);
+    eprintln!("Overbuff: {}", upstream.overbuff);
+    match mock.serve(addr).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
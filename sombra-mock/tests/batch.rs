@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use sombra::{
+    BatchField, BatchRequest, Battletag, CachedClient, Client, Lifespans, LruStore, ProfilePart,
+};
+use sombra_mock::Mock;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+
+async fn client() -> CachedClient {
+    let upstream = Mock::load(FIXTURES).unwrap().spawn().await.unwrap();
+    let client = Client::with_upstream(upstream).await.unwrap();
+    CachedClient::with_client(client, LruStore::new(Lifespans::new(60, 60, 60), 64))
+}
+
+#[tokio::test]
+async fn failures_are_reported_per_player_and_field() {
+    let client = client().await;
+    let player = Battletag::new("Player", 1234);
+    let broken = Battletag::new("Broken", 1234);
+    let request = BatchRequest {
+        battletags: vec![player.clone(), broken.clone(), player.clone()],
+        fields: vec![BatchField::Found, BatchField::Profile],
+        parts: Vec::new(),
+    };
+    let response = client.batch(&request, 4).await.value;
+    assert_eq!(response.players.len(), 2);
+
+    let item = &response.players[&player.to_string()];
+    assert_eq!(item.errors, []);
+    assert_eq!(item.found.as_ref().unwrap().battle_tag, player);
+    let profile = item.profile.as_ref().unwrap();
+    assert!(!profile.competitive_pc.as_ref().unwrap().is_empty());
+    assert!(item.overbuff.is_none() && item.summary.is_none());
+
+    let item = &response.players[&broken.to_string()];
+    assert!(item.found.is_none() && item.profile.is_none());
+    let fields: Vec<_> = item.errors.iter().map(|e| e.field).collect();
+    assert_eq!(fields, [BatchField::Found, BatchField::Profile]);
+}
+
+#[tokio::test]
+async fn profile_parts_are_limited_to_the_request() {
+    let client = client().await;
+    let player = Battletag::new("Player", 1234);
+    let request = BatchRequest {
+        battletags: vec![player.clone()],
+        fields: vec![BatchField::Profile],
+        parts: vec![ProfilePart::Ranks],
+    };
+    let response = client.batch(&request, 1).await.value;
+    let profile = response.players[&player.to_string()]
+        .profile
+        .clone()
+        .unwrap();
+    assert!(!profile.ranks.is_empty());
+    assert_eq!(profile.competitive_pc, Some(HashMap::new()));
+}
@@ -0,0 +1,78 @@
+use sombra::{Battletag, Client, Error, Mode, Platform, ProfileParts, Selectors};
+use sombra_mock::{Mock, Rule};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+
+async fn client(mock: Mock) -> Client {
+    Client::with_upstream(mock.spawn().await.unwrap())
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn shared_pages_are_served() {
+    let client = client(Mock::load(FIXTURES).unwrap()).await;
+    let player = Battletag::new("Player", 1234);
+    assert!(client.heroes().len() > 30);
+    let profile = client.profile(&player, ProfileParts::ALL).await.unwrap();
+    assert!(!profile.competitive_pc.unwrap().is_empty());
+    assert_eq!(client.overbuff(&player).await.unwrap().ranks.len(), 3);
+
+    let private = Battletag::new("Private", 1234);
+    let profile = client.profile(&private, ProfileParts::ALL).await.unwrap();
+    assert!(profile.visibility.is_private());
+    assert_eq!(profile.stats(Platform::Pc, Mode::Competitive), None);
+}
+
+#[tokio::test]
+async fn scripted_failures_are_served() {
+    let client = client(Mock::load(FIXTURES).unwrap()).await;
+    let broken = Battletag::new("Broken", 1234);
+    assert!(matches!(
+        client.overbuff(&broken).await,
+        Err(Error::Http(status)) if status.as_u16() == 503
+    ));
+
+    let flaky = Battletag::new("Flaky", 1234);
+    assert!(client.profile(&flaky, ProfileParts::ALL).await.is_err());
+    assert!(client.profile(&flaky, ProfileParts::ALL).await.is_ok());
+}
+
+#[tokio::test]
+async fn redesigned_pages_need_new_selectors() {
+    let client = client(Mock::load(FIXTURES).unwrap()).await;
+    let btag = Battletag::new("Redesigned", 1234);
+    let profile = client.profile(&btag, ProfileParts::ALL).await.unwrap();
+    let before = profile.quickplay_pc.unwrap();
+    assert!(!before.is_empty());
+
+    let profile = client.profile(&btag, ProfileParts::ALL).await.unwrap();
+    let after = profile.quickplay_pc.unwrap();
+    assert!(after.values().all(|hero| hero.stats.is_empty()));
+
+    let mut selectors = Selectors::default();
+    selectors.career.stat_item_class = "stat-row".to_owned();
+    selectors.career.stat_name_class = "stat-name".to_owned();
+    selectors.career.stat_value_class = "stat-value".to_owned();
+    client.set_selectors(selectors);
+    let profile = client.profile(&btag, ProfileParts::ALL).await.unwrap();
+    let after = profile.quickplay_pc.unwrap();
+    assert_eq!(after.len(), 3);
+    assert_eq!(after["ana"], before["ana"]);
+}
+
+#[tokio::test]
+async fn rules_added_at_runtime_serve_other_files() {
+    let mock = Mock::new(FIXTURES);
+    mock.rule(
+        Rule::new("/overwatch.blizzard.com/en-us/heroes/")
+            .file("../../sombra/fixtures/heroes/index.html"),
+    );
+    let client = client(mock).await;
+    assert!(client.heroes().len() > 30);
+    let missing = Battletag::new("Player", 1234);
+    assert!(matches!(
+        client.profile(&missing, ProfileParts::ALL).await,
+        Err(Error::Http(status)) if status.as_u16() == 404
+    ));
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    Battletag, FoundPlayer, Overbuff, PlayerProfile, PlayerSummary, ProfilePart, ProfileParts,
+};
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Enum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub enum BatchField {
+    /// Search result of the player
+    Found,
+    Profile,
+    Overbuff,
+    /// Competitive PC [`PlayerSummary`]
+    Summary,
+}
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct BatchRequest {
+    pub battletags: Vec<Battletag>,
+    /// Fields to fetch for every player, all but the summary if empty
+    #[cfg_attr(feature = "poem_openapi", oai(default))]
+    #[serde(default)]
+    pub fields: Vec<BatchField>,
+    /// Parts of the profiles to parse, all of them if empty
+    #[cfg_attr(feature = "poem_openapi", oai(default))]
+    #[serde(default)]
+    pub parts: Vec<ProfilePart>,
+}
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct BatchResponse {
+    /// Keyed by battletag in `Name#1234` form
+    pub players: HashMap<String, BatchItem>,
+}
+
+/// Fields that were not requested or could not be fetched are missing.
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct BatchItem {
+    pub found: Option<FoundPlayer>,
+    pub profile: Option<PlayerProfile>,
+    pub overbuff: Option<Overbuff>,
+    pub summary: Option<PlayerSummary>,
+    pub errors: Vec<BatchError>,
+}
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct BatchError {
+    pub field: BatchField,
+    pub error: String,
+}
+
+impl BatchRequest {
+    /// The requested fields, with an empty selection meaning all but the summary.
+    #[must_use]
+    pub fn fields(&self) -> Vec<BatchField> {
+        if self.fields.is_empty() {
+            vec![BatchField::Found, BatchField::Profile, BatchField::Overbuff]
+        } else {
+            self.fields.clone()
+        }
+    }
+
+    /// The requested profile parts, with an empty selection meaning all of them.
+    #[must_use]
+    pub fn parts(&self) -> ProfileParts {
+        if self.parts.is_empty() {
+            ProfileParts::ALL
+        } else {
+            self.parts.iter().copied().collect()
+        }
+    }
+}
@@ -21,6 +21,12 @@ impl Battletag {
             number,
         }
     }
+
+    /// Whether the name could belong to a player, i.e. consists of letters and digits only.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        !self.name.is_empty() && self.name.chars().all(char::is_alphanumeric)
+    }
 }
 
 impl Debug for Battletag {
@@ -42,9 +48,16 @@ impl Display for Battletag {
 impl FromStr for Battletag {
     type Err = ();
 
+    /// Accepts both `Name#1234` and the URL-safe `Name-1234`.
     #[allow(clippy::map_err_ignore)]
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (name, number) = s.split_once('#').or_else(|| s.split_once('-')).ok_or(())?;
+        let (name, number) = s
+            .rsplit_once('#')
+            .or_else(|| s.rsplit_once('-'))
+            .ok_or(())?;
+        if name.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(());
+        }
         let number = number.parse().map_err(|_| ())?;
         Ok(Self {
             name: name.to_owned(),
@@ -66,3 +79,49 @@ impl From<Battletag> for String {
         val.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_both_separators() {
+        let btag = Battletag::new("Player", 1234);
+        assert_eq!("Player#1234".parse(), Ok(btag.clone()));
+        assert_eq!("Player-1234".parse(), Ok(btag.clone()));
+        assert_eq!(btag.to_string(), "Player#1234");
+        assert_eq!(format!("{btag:#}"), "Player-1234");
+    }
+
+    #[test]
+    fn number_follows_the_last_separator() {
+        assert_eq!(
+            "Some-Name-1234".parse(),
+            Ok(Battletag::new("Some-Name", 1234))
+        );
+        assert_eq!("Name-1#23".parse(), Ok(Battletag::new("Name-1", 23)));
+    }
+
+    #[test]
+    fn rejects_malformed_battletags() {
+        for s in [
+            "Player",
+            "Player#",
+            "#1234",
+            "-1234",
+            "Player#12a",
+            "Player#+12",
+            "",
+        ] {
+            assert_eq!(s.parse::<Battletag>(), Err(()), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn names_are_letters_and_digits() {
+        assert!(Battletag::new("Plâyer2", 1).is_valid());
+        assert!(!Battletag::new("Some-Name", 1).is_valid());
+        assert!(!Battletag::new("a b", 1).is_valid());
+        assert!(!Battletag::new("", 1).is_valid());
+    }
+}
@@ -0,0 +1,79 @@
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::Battletag;
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Enum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub enum CacheKind {
+    Profile,
+    /// Overbuff and any other third-party data source
+    Overbuff,
+    Search,
+}
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct CacheEntryInfo {
+    pub kind: CacheKind,
+    /// Battletag in `Name-1234` form, followed by `?parts` for partial profiles or `@source` for
+    /// third-party data sources, or search term
+    pub key: String,
+    pub inserted: DateTime<Utc>,
+    /// Seconds since the entry was inserted
+    pub age: u64,
+    pub stale: bool,
+}
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct WarmReport {
+    pub loaded: Vec<Battletag>,
+    pub failed: Vec<WarmFailure>,
+}
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct WarmFailure {
+    pub battletag: Battletag,
+    pub error: String,
+}
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct CacheMetrics {
+    pub profile: CacheKindMetrics,
+    pub overbuff: CacheKindMetrics,
+    pub search: CacheKindMetrics,
+}
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct CacheKindMetrics {
+    /// Lookups answered from the cache, including stale values
+    pub hits: u64,
+    /// Lookups that found no usable entry
+    pub misses: u64,
+    /// Entries removed to make room or by request
+    pub evictions: u64,
+    /// Requests sent upstream
+    pub fetches: u64,
+    /// Lookups that joined a request already in flight instead of sending their own
+    pub coalesced: u64,
+    /// Stale values served past their soft TTL
+    pub stale: u64,
+    /// Stale entries kept because they had not changed upstream
+    pub revalidated: u64,
+}
@@ -0,0 +1,69 @@
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+/// Kind of upstream page a parser reads.
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Enum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub enum PageKind {
+    Career,
+    Overbuff,
+    Heroes,
+}
+
+/// Result of running every parser against the canary corpus.
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct DriftReport {
+    pub checked_at: DateTime<Utc>,
+    pub selectors_version: u32,
+    /// Whether any page failed to parse or did not match its expectations
+    pub drifted: bool,
+    pub pages: Vec<PageReport>,
+}
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct PageReport {
+    pub kind: PageKind,
+    /// Path of the recorded page or URL of the fetched one
+    pub page: String,
+    /// Whether the page was fetched for this run
+    pub fresh: bool,
+    /// Missing if the page could not be fetched or parsed
+    pub shape: Option<PageShape>,
+    pub error: Option<String>,
+    #[cfg_attr(feature = "poem_openapi", oai(default))]
+    #[serde(default)]
+    pub drift: Vec<Drift>,
+}
+
+/// What a parser extracted from a page.
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct PageShape {
+    pub private: bool,
+    pub ranks: u64,
+    /// Heroes with stats on career pages, hero cards on the hero overview
+    pub heroes: u64,
+    /// Distinct stat names across all heroes
+    pub stats: u64,
+}
+
+/// A value of a [`PageShape`] that did not match the expectation.
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct Drift {
+    pub field: String,
+    pub expected: String,
+    pub actual: String,
+}
@@ -0,0 +1,35 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// Body of every error response of the API.
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Whether the same request may succeed later
+    pub retryable: bool,
+}
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Enum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCode {
+    BadRequest,
+    InvalidBattletag,
+    Unauthorized,
+    /// The player has hidden their career profile
+    PrivateProfile,
+    NotFound,
+    /// Too many requests, either to the API or from the API to upstream
+    RateLimited,
+    /// Upstream failed or could not be reached
+    Upstream,
+    /// Upstream sent something that could not be parsed, e.g. after a markup change
+    UpstreamParse,
+    /// Upstream took too long to respond
+    Timeout,
+    Internal,
+}
@@ -1,13 +1,27 @@
 mod assets;
+mod batch;
 mod btag;
+mod cache;
+mod drift;
+mod error;
 mod heroes;
 mod overbuff;
 mod profile;
+mod provenance;
 mod search;
+mod selectors;
+mod summary;
 
 pub use assets::*;
+pub use batch::*;
 pub use btag::*;
+pub use cache::*;
+pub use drift::*;
+pub use error::*;
 pub use heroes::*;
 pub use overbuff::*;
 pub use profile::*;
+pub use provenance::*;
 pub use search::*;
+pub use selectors::*;
+pub use summary::*;
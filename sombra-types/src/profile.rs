@@ -18,12 +18,24 @@ pub struct PlayerProfile {
     pub endorsement: Option<Endorsement>,
     pub portrait: Url,
     pub ranks: Vec<Rank>,
+    pub visibility: ProfileVisibility,
+    /// Whether `visibility` is private, kept for v1 clients
     pub private: bool,
     pub last_updated: DateTime<Utc>,
-    pub quickplay_console: HashMap<String, HeroStats>,
-    pub competitive_console: HashMap<String, HeroStats>,
-    pub quickplay_pc: HashMap<String, HeroStats>,
-    pub competitive_pc: HashMap<String, HeroStats>,
+    /// Hero stats are missing on private profiles, and empty if the mode was never played or
+    /// not parsed
+    #[cfg_attr(feature = "poem_openapi", oai(skip_serializing_if_is_none))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quickplay_console: Option<HashMap<String, HeroStats>>,
+    #[cfg_attr(feature = "poem_openapi", oai(skip_serializing_if_is_none))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub competitive_console: Option<HashMap<String, HeroStats>>,
+    #[cfg_attr(feature = "poem_openapi", oai(skip_serializing_if_is_none))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quickplay_pc: Option<HashMap<String, HeroStats>>,
+    #[cfg_attr(feature = "poem_openapi", oai(skip_serializing_if_is_none))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub competitive_pc: Option<HashMap<String, HeroStats>>,
 }
 
 #[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
@@ -36,10 +48,79 @@ pub struct PlayerProfileReduced {
     pub endorsement: Option<Endorsement>,
     pub portrait: Url,
     pub ranks: Vec<Rank>,
+    pub visibility: ProfileVisibility,
+    /// Whether `visibility` is private, kept for v1 clients
     pub private: bool,
     pub last_updated: DateTime<Utc>,
 }
 
+/// Private profiles still show the title, endorsement, portrait and ranks, but no hero stats.
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Enum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub enum ProfileVisibility {
+    Public,
+    Private,
+}
+
+/// Section of a career page. The summary (title, endorsement, portrait, privacy and last update)
+/// is always parsed.
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Enum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub enum ProfilePart {
+    Summary,
+    Ranks,
+    Pc,
+    Console,
+    Quickplay,
+    Competitive,
+}
+
+/// Sections of a career page to parse. Hero stats are parsed for every selected platform and
+/// mode; selecting only platforms includes both modes and vice versa.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProfileParts {
+    pub ranks: bool,
+    pub pc: bool,
+    pub console: bool,
+    pub quickplay: bool,
+    pub competitive: bool,
+}
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Enum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub enum Platform {
+    Pc,
+    Console,
+}
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Enum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub enum Mode {
+    Quickplay,
+    Competitive,
+}
+
+/// Stats of a single hero on every platform and mode it was played on.
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerHeroStats {
+    pub hero: String,
+    pub quickplay_console: Option<HeroStats>,
+    pub competitive_console: Option<HeroStats>,
+    pub quickplay_pc: Option<HeroStats>,
+    pub competitive_pc: Option<HeroStats>,
+}
+
 bounded_integer::bounded_integer! {
     #[cfg_attr(feature = "poem_openapi", derive(poem_openapi::NewType))]
     pub struct Endorsement{ 1..=5 }
@@ -101,6 +182,209 @@ impl Display for Rank {
     }
 }
 
+impl ProfileParts {
+    pub const SUMMARY: Self = Self {
+        ranks: false,
+        pc: false,
+        console: false,
+        quickplay: false,
+        competitive: false,
+    };
+    /// Everything in [`PlayerProfileReduced`]
+    pub const REDUCED: Self = Self {
+        ranks: true,
+        ..Self::SUMMARY
+    };
+    pub const ALL: Self = Self {
+        ranks: true,
+        pc: true,
+        console: true,
+        quickplay: true,
+        competitive: true,
+    };
+    /// Every hero stat, but no ranks
+    pub const STATS: Self = Self {
+        ranks: false,
+        ..Self::ALL
+    };
+
+    /// Only the hero stats of `platform` in `mode`
+    #[must_use]
+    pub const fn table(platform: Platform, mode: Mode) -> Self {
+        Self {
+            pc: matches!(platform, Platform::Pc),
+            console: matches!(platform, Platform::Console),
+            quickplay: matches!(mode, Mode::Quickplay),
+            competitive: matches!(mode, Mode::Competitive),
+            ..Self::SUMMARY
+        }
+    }
+
+    /// Whether the hero stats of a platform and mode are selected.
+    #[must_use]
+    pub const fn stats(&self, console: bool, competitive: bool) -> bool {
+        let platform = if console { self.console } else { self.pc };
+        let mode = if competitive {
+            self.competitive
+        } else {
+            self.quickplay
+        };
+        platform && mode
+    }
+
+    #[must_use]
+    pub const fn contains(&self, other: Self) -> bool {
+        (self.ranks || !other.ranks)
+            && (self.pc || !other.pc)
+            && (self.console || !other.console)
+            && (self.quickplay || !other.quickplay)
+            && (self.competitive || !other.competitive)
+    }
+
+    /// The parts that make up this selection, starting with [`ProfilePart::Summary`].
+    #[must_use]
+    pub fn parts(&self) -> Vec<ProfilePart> {
+        let mut parts = vec![ProfilePart::Summary];
+        for (selected, part) in [
+            (self.ranks, ProfilePart::Ranks),
+            (self.pc, ProfilePart::Pc),
+            (self.console, ProfilePart::Console),
+            (self.quickplay, ProfilePart::Quickplay),
+            (self.competitive, ProfilePart::Competitive),
+        ] {
+            if selected {
+                parts.push(part);
+            }
+        }
+        parts
+    }
+}
+
+impl Default for ProfileParts {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl FromIterator<ProfilePart> for ProfileParts {
+    fn from_iter<T: IntoIterator<Item = ProfilePart>>(iter: T) -> Self {
+        let mut parts = Self::SUMMARY;
+        for part in iter {
+            match part {
+                ProfilePart::Summary => {}
+                ProfilePart::Ranks => parts.ranks = true,
+                ProfilePart::Pc => parts.pc = true,
+                ProfilePart::Console => parts.console = true,
+                ProfilePart::Quickplay => parts.quickplay = true,
+                ProfilePart::Competitive => parts.competitive = true,
+            }
+        }
+        let platform = parts.pc || parts.console;
+        let mode = parts.quickplay || parts.competitive;
+        if platform && !mode {
+            parts.quickplay = true;
+            parts.competitive = true;
+        } else if mode && !platform {
+            parts.pc = true;
+            parts.console = true;
+        }
+        parts
+    }
+}
+
+impl Display for ProfileParts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<_> = self.parts().iter().map(ProfilePart::as_str).collect();
+        f.write_str(&parts.join(","))
+    }
+}
+
+impl ProfilePart {
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Summary => "summary",
+            Self::Ranks => "ranks",
+            Self::Pc => "pc",
+            Self::Console => "console",
+            Self::Quickplay => "quickplay",
+            Self::Competitive => "competitive",
+        }
+    }
+}
+
+impl ProfileVisibility {
+    #[must_use]
+    pub const fn is_private(self) -> bool {
+        matches!(self, Self::Private)
+    }
+}
+
+impl PlayerProfile {
+    /// `None` if the profile is private, so that hidden stats can be told apart from a mode
+    /// that was never played.
+    #[must_use]
+    pub const fn stats(
+        &self,
+        platform: Platform,
+        mode: Mode,
+    ) -> Option<&HashMap<String, HeroStats>> {
+        match (platform, mode) {
+            (Platform::Pc, Mode::Quickplay) => self.quickplay_pc.as_ref(),
+            (Platform::Pc, Mode::Competitive) => self.competitive_pc.as_ref(),
+            (Platform::Console, Mode::Quickplay) => self.quickplay_console.as_ref(),
+            (Platform::Console, Mode::Competitive) => self.competitive_console.as_ref(),
+        }
+    }
+
+    /// Hero stats of PC quickplay and competitive, then those of console. None on private
+    /// profiles.
+    pub fn tables(&self) -> impl Iterator<Item = &HashMap<String, HeroStats>> {
+        [
+            &self.quickplay_pc,
+            &self.competitive_pc,
+            &self.quickplay_console,
+            &self.competitive_console,
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// Finds a hero by name, ignoring case, punctuation and accents, so that `soldier-76`
+    /// matches `Soldier: 76`.
+    #[must_use]
+    pub fn hero_stats(&self, hero: &str) -> Option<PlayerHeroStats> {
+        let slug = hero_slug(hero);
+        let name = self
+            .tables()
+            .flat_map(HashMap::keys)
+            .find(|name| hero_slug(name) == slug)?;
+        let get = |table: &Option<HashMap<String, HeroStats>>| table.as_ref()?.get(name).cloned();
+        Some(PlayerHeroStats {
+            hero: name.clone(),
+            quickplay_console: get(&self.quickplay_console),
+            competitive_console: get(&self.competitive_console),
+            quickplay_pc: get(&self.quickplay_pc),
+            competitive_pc: get(&self.competitive_pc),
+        })
+    }
+}
+
+fn hero_slug(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            'á' | 'à' | 'â' | 'ä' | 'ã' => 'a',
+            'é' | 'è' | 'ê' | 'ë' => 'e',
+            'í' | 'ì' | 'î' | 'ï' => 'i',
+            'ó' | 'ò' | 'ô' | 'ö' | 'õ' => 'o',
+            'ú' | 'ù' | 'û' | 'ü' => 'u',
+            c => c,
+        })
+        .collect()
+}
+
 impl From<&PlayerProfile> for PlayerProfileReduced {
     fn from(value: &PlayerProfile) -> Self {
         Self {
@@ -109,6 +393,7 @@ impl From<&PlayerProfile> for PlayerProfileReduced {
             endorsement: value.endorsement,
             portrait: value.portrait.clone(),
             ranks: value.ranks.clone(),
+            visibility: value.visibility,
             private: value.private,
             last_updated: value.last_updated,
         }
@@ -298,3 +583,17 @@ impl std::iter::Sum<Stat> for Option<Stat> {
         Some(sum)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hero_slugs_ignore_case_punctuation_and_accents() {
+        assert_eq!(hero_slug("Soldier: 76"), "soldier76");
+        assert_eq!(hero_slug("soldier-76"), "soldier76");
+        assert_eq!(hero_slug("Lúcio"), "lucio");
+        assert_eq!(hero_slug("Torbjörn"), "torbjorn");
+        assert_eq!(hero_slug("D.Va"), hero_slug("dva"));
+    }
+}
@@ -0,0 +1,55 @@
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{PlayerHeroStats, Rank, Role};
+
+/// What a data source knows about a player.
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct SourceData {
+    pub ranks: Vec<Rank>,
+    #[cfg_attr(feature = "poem_openapi", oai(default))]
+    #[serde(default)]
+    pub heroes: Vec<PlayerHeroStats>,
+    /// When the source last saw the player's data change, if it knows
+    #[cfg_attr(feature = "poem_openapi", oai(skip_serializing_if_is_none))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_updated: Option<DateTime<Utc>>,
+}
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct SourcedRank {
+    pub rank: Rank,
+    /// Name of the data source, such as `careerPage` or `overbuff`
+    pub source: String,
+    /// When the source last saw the data change, or else when it was fetched. Sources fetched
+    /// for the same lookup without a time of their own share the same instant.
+    pub observed: DateTime<Utc>,
+}
+
+/// Sources that disagree on the rank of a role.
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct RankConflict {
+    pub role: Role,
+    pub console: bool,
+    /// Every observation, starting with the one that was chosen
+    pub ranks: Vec<SourcedRank>,
+}
+
+/// Ranks merged from every source, with the most recent observation winning.
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct ReconciledRanks {
+    pub ranks: Vec<SourcedRank>,
+    pub conflicts: Vec<RankConflict>,
+}
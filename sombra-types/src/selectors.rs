@@ -0,0 +1,15 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// Outcome of loading a selector configuration.
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct SelectorsReport {
+    /// Version of the loaded configuration, if it could be parsed
+    pub version: Option<u32>,
+    /// Whether the configuration replaced the one in use
+    pub applied: bool,
+    /// Syntax errors and fixture pages that could not be parsed
+    pub errors: Vec<String>,
+}
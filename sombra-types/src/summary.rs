@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    Battletag, Endorsement, Mode, Platform, ProfileVisibility, RankConflict, Role, SourcedRank,
+    Stat,
+};
+
+/// Everything needed to display a player, merged from the search, career page and Overbuff.
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerSummary {
+    pub battletag: Battletag,
+    pub namecard: Option<Url>,
+    pub portrait: Option<Url>,
+    pub title: Option<String>,
+    pub endorsement: Option<Endorsement>,
+    pub visibility: ProfileVisibility,
+    /// Whether `visibility` is private, kept for v1 clients
+    pub private: bool,
+    pub last_updated: DateTime<Utc>,
+    pub ranks: Vec<SourcedRank>,
+    /// Roles the sources disagree on
+    pub rank_conflicts: Vec<RankConflict>,
+    /// Platform and mode the stats are taken from
+    pub platform: Platform,
+    pub mode: Mode,
+    /// Stats over all heroes
+    pub stats: Option<PlayStats>,
+    pub roles: Vec<RoleStats>,
+    /// Most played heroes first
+    pub top_heroes: Vec<TopHero>,
+}
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct PlayStats {
+    /// Seconds
+    pub time_played: u64,
+    pub wins: u64,
+    pub ties: u64,
+    pub losses: u64,
+}
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct RoleStats {
+    pub role: Role,
+    pub stats: PlayStats,
+}
+
+#[cfg_attr(feature = "poem_openapi", derive(poem_openapi::Object))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "poem_openapi", oai(rename_all = "camelCase"))]
+#[serde(rename_all = "camelCase")]
+pub struct TopHero {
+    pub hero: String,
+    pub role: Role,
+    /// Seconds
+    pub time_played: u64,
+    /// Headline stats like time played, win percentage and weapon accuracy
+    pub stats: HashMap<String, Stat>,
+}
@@ -0,0 +1,104 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sombra::{
+    Battletag, CacheKey, CacheStore, HeroStats, Lifespans, LruStore, PlayerProfile, ProfileParts,
+    ProfileVisibility, Stat, DEFAULT_CAPACITY,
+};
+
+const HEROES: usize = 40;
+const STATS: usize = 50;
+
+/// A profile roughly the size of one with every hero played on every platform and mode.
+fn profile(btag: &Battletag) -> PlayerProfile {
+    let heroes = || -> HashMap<String, HeroStats> {
+        (0..HEROES)
+            .map(|hero| {
+                let stats = (0..STATS)
+                    .map(|stat| (format!("Stat {stat}"), Stat::Number(stat as f64)))
+                    .collect();
+                (format!("hero-{hero}"), HeroStats { stats })
+            })
+            .collect()
+    };
+    PlayerProfile {
+        battletag: btag.clone(),
+        title: Some("Title".to_owned()),
+        endorsement: None,
+        portrait: "https://example.com/portrait.png".parse().unwrap(),
+        ranks: Vec::new(),
+        visibility: ProfileVisibility::Public,
+        private: false,
+        last_updated: Utc::now(),
+        quickplay_console: Some(heroes()),
+        competitive_console: Some(heroes()),
+        quickplay_pc: Some(heroes()),
+        competitive_pc: Some(heroes()),
+    }
+}
+
+/// Runs `hit` `iters` times on each of `threads` threads and returns the slowest thread's time.
+fn concurrent(threads: u64, iters: u64, hit: &(impl Fn() + Sync)) -> Duration {
+    std::thread::scope(|s| {
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                s.spawn(|| {
+                    let start = Instant::now();
+                    for _ in 0..iters {
+                        hit();
+                    }
+                    start.elapsed()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .max()
+            .unwrap_or_default()
+    })
+}
+
+fn cache_hit(c: &mut Criterion) {
+    let btag = Battletag::new("Player", 1234);
+    let key = CacheKey::profile(&btag, ProfileParts::ALL);
+    let store = LruStore::new(
+        Lifespans::new(u64::MAX, u64::MAX, u64::MAX),
+        DEFAULT_CAPACITY,
+    );
+    store.set(key.clone(), Arc::new(profile(&btag)));
+
+    let mut group = c.benchmark_group("cache_hit");
+    for threads in [1, 4, 16] {
+        group.throughput(Throughput::Elements(threads));
+        group.bench_with_input(BenchmarkId::new("arc", threads), &threads, |b, &threads| {
+            b.iter_custom(|iters| {
+                concurrent(threads, iters, &|| {
+                    let entry = store.get::<PlayerProfile>(&key).unwrap();
+                    criterion::black_box(entry.value);
+                })
+            });
+        });
+        group.bench_with_input(
+            BenchmarkId::new("deep_clone", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    concurrent(threads, iters, &|| {
+                        let entry = store.get::<PlayerProfile>(&key).unwrap();
+                        criterion::black_box((*entry.value).clone());
+                    })
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, cache_hit);
+criterion_main!(benches);
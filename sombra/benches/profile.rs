@@ -0,0 +1,130 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sombra::{parse_profile, Battletag, HeroStats, ProfileParts, Selectors};
+use tl::{HTMLTag, ParserOptions, VDom};
+
+/// Parses every career page saved in `fixtures/career`, in full and only what
+/// [`PlayerProfileReduced`](sombra::PlayerProfileReduced) needs, and the hero stats with the
+/// per-option `query_selector` lookups they were parsed with before the single pass.
+fn parse(c: &mut Criterion) {
+    let btag = Battletag::new("Player", 1234);
+    let selectors = Selectors::default();
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/career");
+    let mut pages: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "html"))
+        .collect();
+    pages.sort();
+
+    let mut group = c.benchmark_group("parse_profile");
+    for path in pages {
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let html = fs::read_to_string(&path).unwrap();
+        group.throughput(Throughput::Bytes(html.len() as u64));
+        for (parts, label) in [
+            (ProfileParts::ALL, "all"),
+            (ProfileParts::REDUCED, "reduced"),
+        ] {
+            parse_profile(&btag, &html, parts, &selectors).unwrap();
+            group.bench_with_input(BenchmarkId::new(label, &name), &html, |b, html| {
+                b.iter(|| parse_profile(&btag, html, parts, &selectors).unwrap());
+            });
+        }
+
+        let profile = parse_profile(&btag, &html, ProfileParts::ALL, &selectors).unwrap();
+        let tables = query_selector_stats(&html);
+        assert!(
+            tables.iter().eq(profile.tables()),
+            "baseline differs on {name}"
+        );
+        group.bench_with_input(
+            BenchmarkId::new("query_selector", &name),
+            &html,
+            |b, html| {
+                b.iter(|| query_selector_stats(html));
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Hero stats of PC quickplay and competitive, then those of console, looked up the way they
+/// were before the single pass. None on private profiles.
+fn query_selector_stats(html: &str) -> Vec<HashMap<String, HeroStats>> {
+    let dom = tl::parse(html, ParserOptions::new()).unwrap();
+    if find(&dom, ".Profile-player--privateText").is_some() {
+        return Vec::new();
+    }
+    [(true, false), (false, false), (true, true), (false, true)]
+        .map(|(qp, console)| hero_stats(&dom, qp, console))
+        .into()
+}
+
+fn hero_stats<'dom>(dom: &'dom VDom<'dom>, qp: bool, console: bool) -> HashMap<String, HeroStats> {
+    let mut heroes = HashMap::new();
+    let view_selector = if console {
+        ".Profile-view.controller-view"
+    } else {
+        ".Profile-view.mouseKeyboard-view"
+    };
+    let container_selector = if qp {
+        ".stats.quickPlay-view"
+    } else {
+        ".stats.competitive-view"
+    };
+    let Some(container) = find(dom, view_selector).and_then(|v| find2(dom, v, container_selector))
+    else {
+        return heroes;
+    };
+    let Some(select) = find2(dom, container, ".Profile-dropdown") else {
+        return heroes;
+    };
+    let mut stat_ids = HashMap::new();
+    for option in find_all2(dom, select, "option") {
+        let id = option.attributes().get("value").flatten().unwrap();
+        stat_ids.insert(id.as_utf8_str(), option.inner_text(dom.parser()));
+    }
+    for (id, hero) in stat_ids {
+        let stats_selector = format!(".stats-container.option-{id}");
+        let stats = find2(dom, container, &stats_selector).unwrap();
+        let mut hero_stats = HashMap::new();
+        for stat in find_all2(dom, stats, ".stat-item") {
+            let name = find2(dom, stat, ".name").unwrap().inner_text(dom.parser());
+            let value = find2(dom, stat, ".value").unwrap().inner_text(dom.parser());
+            hero_stats.insert(name.into_owned(), value.parse().unwrap());
+        }
+        heroes.insert(hero.into_owned(), HeroStats { stats: hero_stats });
+    }
+    heroes
+}
+
+fn find<'dom>(dom: &'dom VDom<'dom>, selector: &str) -> Option<&'dom HTMLTag<'dom>> {
+    dom.query_selector(selector)?
+        .next()?
+        .get(dom.parser())?
+        .as_tag()
+}
+
+fn find2<'dom>(
+    dom: &'dom VDom<'dom>,
+    tag: &'dom HTMLTag<'dom>,
+    selector: &'dom str,
+) -> Option<&'dom HTMLTag<'dom>> {
+    find_all2(dom, tag, selector).next()
+}
+
+fn find_all2<'dom>(
+    dom: &'dom VDom<'dom>,
+    tag: &'dom HTMLTag<'dom>,
+    selector: &'dom str,
+) -> impl Iterator<Item = &'dom HTMLTag<'dom>> {
+    tag.query_selector(dom.parser(), selector)
+        .into_iter()
+        .flatten()
+        .filter_map(|n| n.get(dom.parser())?.as_tag())
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -8,9 +8,7 @@ use crate::{Client, Error};
 impl Client {
     #[instrument(level = "debug", skip(self))]
     pub async fn fetch_assets(&mut self) -> crate::Result<()> {
-        let html = self
-            .get("https://overwatch.blizzard.com/en-us/search/")
-            .await?;
+        let html = self.get(&self.upstream.assets_url()).await?;
         let mut split = html.split("const ").skip(2);
 
         let avatars: HashMap<Id, Asset> = parse_json_var(split.next().ok_or_else(Error::parse)?)?;
@@ -0,0 +1,63 @@
+//! Runs every parser against the canary pages and prints the drift report as JSON.
+//!
+//! Usage: `canary [FIXTURES] [--fresh] [--selectors FILE]`
+//!
+//! Fresh pages are only fetched with `--fresh`. Exits with status 1 if anything drifted.
+
+use std::{path::PathBuf, process::ExitCode};
+
+use sombra::{Canary, Client, Selectors};
+
+const DEFAULT_FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+
+fn main() -> ExitCode {
+    let mut fixtures = PathBuf::from(DEFAULT_FIXTURES);
+    let mut selectors = None;
+    let mut fresh = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fresh" => fresh = true,
+            "--selectors" => selectors = args.next(),
+            _ => fixtures = arg.into(),
+        }
+    }
+
+    match run(fixtures, selectors, fresh) {
+        Ok(report) => {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            if report.drifted {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(
+    fixtures: PathBuf,
+    selectors: Option<String>,
+    fresh: bool,
+) -> sombra::Result<sombra::DriftReport> {
+    let canary = Canary::load(fixtures)?;
+    let selectors = match selectors {
+        Some(path) => Selectors::load(path)?,
+        None => Selectors::default(),
+    };
+    if !fresh {
+        return Ok(canary.check_recorded(&selectors));
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let client = Client::new().await?;
+        client.set_selectors(selectors);
+        Ok(canary.check(&client, true).await)
+    })
+}
@@ -1,19 +1,89 @@
-use std::collections::HashMap;
+use std::{
+    any::Any,
+    collections::HashMap,
+    future::Future,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use cached::{Cached, TimedCache};
+use chrono::Utc;
+use futures::{
+    future::{BoxFuture, Shared},
+    FutureExt, StreamExt,
+};
 use parking_lot::Mutex;
+use reqwest::StatusCode;
 use sombra_types::{
-    Asset, Battletag, FoundPlayer, Hero, Id, Overbuff, PlayerProfile, PlayerProfileReduced,
+    Asset, BatchError, BatchField, BatchItem, BatchRequest, BatchResponse, Battletag,
+    CacheEntryInfo, CacheKind, CacheMetrics, DriftReport, FoundPlayer, Hero, Id, Mode, Overbuff,
+    Platform, PlayerProfile, PlayerProfileReduced, ProfileParts, SourceData, WarmFailure,
+    WarmReport,
+};
+
+use crate::{
+    metrics::Metrics, profile::source_data, CacheEntry, CacheKey, CacheStore, CacheValue, Canary,
+    CareerPageSource, Client, DataSource, Error, Lifespans, LruStore, OverbuffSource, Quarantine,
+    RegisteredSource, Selectors, DEFAULT_CAPACITY,
 };
 
-use crate::Client;
+/// Upstream request shared by every caller asking for the same key while it is running.
+type Flight<V> = Shared<BoxFuture<'static, Result<Arc<V>, Arc<Error>>>>;
+
+type Running = HashMap<CacheKey, Box<dyn Any + Send + Sync>>;
+
+/// Flights in progress, and how many have finished so that a lookup that missed the store can
+/// tell whether one filled it since without reading the store under the lock.
+#[derive(Default)]
+struct Flights {
+    running: Mutex<Running>,
+    /// Only incremented while `running` is locked
+    finished: AtomicU64,
+}
+
+/// The lifespans of the [`CacheStore`] act as hard TTLs. Once an entry is older than its soft
+/// TTL it is considered stale and refreshed according to [`Revalidate`].
+pub struct CachedClient<S = LruStore> {
+    client: Arc<Client>,
+    store: Arc<S>,
+    flights: Arc<Flights>,
+    metrics: Arc<Metrics>,
+    soft: Option<(Lifespans, Revalidate)>,
+    profile_expiry: ProfileExpiry,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileExpiry {
+    /// Refetch stale profiles
+    Ttl,
+    /// Refetch stale profiles only if the search result reports a newer `last_updated`
+    LastUpdated,
+}
 
-#[derive(Debug)]
-pub struct CachedClient {
-    client: Client,
-    profile_cache: Mutex<TimedCache<Battletag, PlayerProfile>>,
-    overbuff_cache: Mutex<TimedCache<Battletag, Overbuff>>,
-    search_cache: Mutex<TimedCache<String, Vec<FoundPlayer>>>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Revalidate {
+    /// Serve stale values immediately and refresh them in the background
+    Background,
+    /// Refresh stale values before responding, serving them only if the refresh fails
+    OnError,
+}
+
+/// Value returned by [`CachedClient`] along with how old it is. Cached values are shared with
+/// the store rather than cloned on every hit.
+#[derive(Debug, Clone)]
+pub struct Cached<T> {
+    pub value: T,
+    pub age: Duration,
+    /// Older than the soft TTL
+    pub stale: bool,
+    /// How much longer the value is served without revalidation, zero once it is stale
+    pub max_age: Duration,
+    /// Upstream fetches made for this call rather than serving from the store, more than one
+    /// for values combined from several
+    pub fetches: u32,
 }
 
 impl CachedClient {
@@ -22,12 +92,8 @@ impl CachedClient {
         overbuff_lifespan: u64,
         search_lifespan: u64,
     ) -> crate::Result<Self> {
-        Ok(Self {
-            client: Client::new().await?,
-            profile_cache: Mutex::new(TimedCache::with_lifespan(profile_lifespan)),
-            overbuff_cache: Mutex::new(TimedCache::with_lifespan(overbuff_lifespan)),
-            search_cache: Mutex::new(TimedCache::with_lifespan(search_lifespan)),
-        })
+        let lifespans = Lifespans::new(profile_lifespan, overbuff_lifespan, search_lifespan);
+        Self::with_store(LruStore::new(lifespans, DEFAULT_CAPACITY)).await
     }
 
     pub async fn new_default() -> crate::Result<Self> {
@@ -38,66 +104,993 @@ impl CachedClient {
         Self::new(u64::MAX, u64::MAX, u64::MAX).await
     }
 
-    pub async fn profile_full(&self, btag: &Battletag) -> crate::Result<PlayerProfile> {
-        {
-            let mut cache = self.profile_cache.lock();
-            if let Some(profile) = cache.cache_get(btag) {
-                return Ok(profile.clone());
+    /// Like [`new`](Self::new), but entries are persisted to `dir` and reloaded from it.
+    pub async fn new_persistent(
+        dir: impl Into<PathBuf>,
+        profile_lifespan: u64,
+        overbuff_lifespan: u64,
+        search_lifespan: u64,
+    ) -> crate::Result<Self> {
+        let lifespans = Lifespans::new(profile_lifespan, overbuff_lifespan, search_lifespan);
+        Self::with_store(LruStore::persistent(lifespans, DEFAULT_CAPACITY, dir)?).await
+    }
+}
+
+impl<S: CacheStore + 'static> CachedClient<S> {
+    pub async fn with_store(store: S) -> crate::Result<Self> {
+        Ok(Self::with_client(Client::new().await?, store))
+    }
+
+    /// Caches the responses of an already configured `client` in `store`.
+    pub fn with_client(client: Client, store: S) -> Self {
+        Self {
+            client: Arc::new(client),
+            store: Arc::new(store),
+            flights: Arc::default(),
+            metrics: Arc::default(),
+            soft: None,
+            profile_expiry: ProfileExpiry::Ttl,
+        }
+    }
+
+    /// Serves entries older than `soft` while refreshing them in the background.
+    #[must_use]
+    pub const fn stale_while_revalidate(mut self, soft: Lifespans) -> Self {
+        self.soft = Some((soft, Revalidate::Background));
+        self
+    }
+
+    /// Refreshes entries older than `soft`, but serves them anyway if the refresh fails.
+    #[must_use]
+    pub const fn stale_if_error(mut self, soft: Lifespans) -> Self {
+        self.soft = Some((soft, Revalidate::OnError));
+        self
+    }
+
+    /// Chooses how stale profiles are revalidated. Only has an effect with a soft TTL.
+    #[must_use]
+    pub const fn profile_expiry(mut self, expiry: ProfileExpiry) -> Self {
+        self.profile_expiry = expiry;
+        self
+    }
+
+    /// Writes pages that fail to parse to `quarantine`.
+    #[must_use]
+    pub fn quarantine(self, quarantine: Quarantine) -> Self {
+        self.client.set_quarantine(Some(quarantine));
+        self
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn flush(&self) {
+        self.store.flush();
+    }
+
+    pub fn metrics(&self) -> CacheMetrics {
+        self.metrics.snapshot()
+    }
+
+    pub async fn profile_full(
+        &self,
+        btag: &Battletag,
+    ) -> crate::Result<Cached<Arc<PlayerProfile>>> {
+        self.profile_parts(btag, ProfileParts::ALL).await
+    }
+
+    pub async fn profile(&self, btag: &Battletag) -> crate::Result<Cached<PlayerProfileReduced>> {
+        Ok(self
+            .profile_parts(btag, ProfileParts::REDUCED)
+            .await?
+            .map(|p| (&*p).into()))
+    }
+
+    /// Each selection of parts is cached on its own, but a fresh full profile is served for any
+    /// of them.
+    pub async fn profile_parts(
+        &self,
+        btag: &Battletag,
+        parts: ProfileParts,
+    ) -> crate::Result<Cached<Arc<PlayerProfile>>> {
+        if parts != ProfileParts::ALL {
+            let full = self.store.get(&CacheKey::profile(btag, ProfileParts::ALL));
+            if let Some(full) = full.filter(|e| !self.is_stale(CacheKind::Profile, e.age())) {
+                self.metrics.get(CacheKind::Profile).hit();
+                let max_age = self.max_age(CacheKind::Profile, full.age());
+                return Ok(Cached {
+                    max_age,
+                    ..full.into()
+                });
             }
         }
-        let profile = self.client.profile(btag).await?;
-        self.profile_cache
-            .lock()
-            .cache_set(btag.clone(), profile.clone());
-        Ok(profile)
+        let btag = btag.clone();
+        let this = self.clone();
+        let key = CacheKey::profile(&btag, parts);
+        self.cached(key, |client, previous| async move {
+            let expiry = this.profile_expiry;
+            if let Some(previous) = previous.filter(|_| expiry == ProfileExpiry::LastUpdated) {
+                if this.unchanged(&previous).await {
+                    this.metrics.get(CacheKind::Profile).revalidate();
+                    return Ok(previous);
+                }
+            }
+            client.profile(&btag, parts).await.map(Arc::new)
+        })
+        .await
+    }
+
+    pub async fn overbuff(&self, btag: &Battletag) -> crate::Result<Cached<Arc<Overbuff>>> {
+        Ok(self.source(OverbuffSource::NAME, btag).await?.map(|data| {
+            Arc::new(Overbuff {
+                ranks: data.ranks.clone(),
+            })
+        }))
     }
 
-    pub async fn profile(&self, btag: &Battletag) -> crate::Result<PlayerProfileReduced> {
-        {
-            let mut cache = self.profile_cache.lock();
-            if let Some(profile) = cache.cache_get(btag) {
-                return Ok(profile.into());
+    /// Data of `btag` from the source called `name`. The career page is served from the cached
+    /// full profile.
+    pub async fn source(
+        &self,
+        name: &str,
+        btag: &Battletag,
+    ) -> crate::Result<Cached<Arc<SourceData>>> {
+        if name == CareerPageSource::NAME {
+            let profile = self.profile_full(btag).await?;
+            return Ok(profile.map(|p| Arc::new(source_data(&p))));
+        }
+        // entries of a source may outlive its registration in a persistent store
+        if !self.sources().iter().any(|s| s.source.name() == name) {
+            return Err(Error::UnknownSource(name.to_owned()));
+        }
+        let name = name.to_owned();
+        let btag = btag.clone();
+        self.cached(CacheKey::source(&btag, &name), |client, _| async move {
+            client.source(&name, &btag).await.map(Arc::new)
+        })
+        .await
+    }
+
+    /// Adds `source`, replacing any source with the same name. See [`Client::register_source`].
+    pub fn register_source(&self, source: impl DataSource + 'static, priority: i32) {
+        self.client.register_source(source, priority);
+    }
+
+    /// Removes the source called `name`. See [`Client::unregister_source`].
+    pub fn unregister_source(&self, name: &str) -> bool {
+        self.client.unregister_source(name)
+    }
+
+    /// The registered sources, highest priority first.
+    pub fn sources(&self) -> Vec<RegisteredSource> {
+        self.client.sources()
+    }
+
+    pub fn selectors(&self) -> Arc<Selectors> {
+        self.client.selectors()
+    }
+
+    /// Replaces the selectors used by every following request. Cached values are kept.
+    pub fn set_selectors(&self, selectors: Selectors) {
+        self.client.set_selectors(selectors);
+    }
+
+    /// Runs `canary` with the selectors in use, fetching fresh pages if `fresh` is set.
+    pub async fn canary(&self, canary: &Canary, fresh: bool) -> DriftReport {
+        canary.check(&self.client, fresh).await
+    }
+
+    pub async fn search(&self, name: &str) -> crate::Result<Cached<Arc<Vec<FoundPlayer>>>> {
+        let name = name.to_owned();
+        self.cached(CacheKey::search(&name), |client, _| async move {
+            client.search(&name).await.map(Arc::new)
+        })
+        .await
+    }
+
+    /// The search result of exactly `btag`.
+    pub async fn found(&self, btag: &Battletag) -> crate::Result<Cached<FoundPlayer>> {
+        let found = self.search(&btag.name).await?;
+        let player = found
+            .value
+            .iter()
+            .find(|f| f.battle_tag == *btag)
+            .cloned()
+            .ok_or(Error::Http(StatusCode::NOT_FOUND))?;
+        Ok(found.map(|_| player))
+    }
+
+    pub fn assets(&self) -> &HashMap<Id, Asset> {
+        self.client.assets()
+    }
+
+    pub fn heroes(&self) -> &[Hero] {
+        self.client.heroes()
+    }
+
+    pub fn entries(&self) -> Vec<CacheEntryInfo> {
+        let mut entries: Vec<_> = self
+            .store
+            .entries()
+            .into_iter()
+            .map(|(key, inserted)| {
+                let age = (Utc::now() - inserted).to_std().unwrap_or_default();
+                CacheEntryInfo {
+                    stale: self.is_stale(key.kind, age),
+                    kind: key.kind,
+                    key: key.key,
+                    inserted,
+                    age: age.as_secs(),
+                }
+            })
+            .collect();
+        entries.sort_by_key(|e| e.inserted);
+        entries
+    }
+
+    /// Removes the profile and Overbuff entries of a player.
+    pub fn evict_player(&self, btag: &Battletag) -> bool {
+        let mut removed = false;
+        for key in self.store.keys() {
+            if key.is_player(btag) {
+                removed |= self.evict(&key);
             }
         }
-        let profile = self.client.profile(btag).await?;
-        let reduced = (&profile).into();
-        self.profile_cache.lock().cache_set(btag.clone(), profile);
-        Ok(reduced)
+        removed
+    }
+
+    pub fn evict_search(&self, name: &str) -> bool {
+        self.evict(&CacheKey::search(name))
     }
 
-    pub async fn overbuff(&self, btag: &Battletag) -> crate::Result<Overbuff> {
-        {
-            let mut cache = self.overbuff_cache.lock();
-            if let Some(profile) = cache.cache_get(btag) {
-                return Ok(profile.clone());
+    pub fn clear(&self) {
+        for (key, _) in self.store.entries() {
+            self.metrics.get(key.kind).evict();
+        }
+        self.store.clear();
+    }
+
+    /// Fetches a full profile upstream regardless of what is cached and replaces the cached
+    /// entries.
+    pub async fn refresh_profile(&self, btag: &Battletag) -> crate::Result<Arc<PlayerProfile>> {
+        let btag = btag.clone();
+        let key = CacheKey::profile(&btag, ProfileParts::ALL);
+        for partial in self.store.keys() {
+            if partial.kind == CacheKind::Profile && partial != key && partial.is_player(&btag) {
+                self.evict(&partial);
             }
         }
-        let overbuff = self.client.overbuff(btag).await?;
-        self.overbuff_cache
-            .lock()
-            .cache_set(btag.clone(), overbuff.clone());
-        Ok(overbuff)
+        let flight = {
+            let mut flights = self.flights.running.lock();
+            match self.running(&flights, &key) {
+                Some(flight) => flight,
+                None => self.start(&mut flights, key, |client| async move {
+                    client.profile(&btag, ProfileParts::ALL).await.map(Arc::new)
+                }),
+            }
+        };
+        flight.await.map_err(Error::Shared)
     }
 
-    pub async fn search(&self, name: &str) -> crate::Result<Vec<FoundPlayer>> {
-        {
-            let mut cache = self.search_cache.lock();
-            if let Some(profile) = cache.cache_get(name) {
-                return Ok(profile.clone());
+    /// Loads the profiles of `btags` into the cache, fetching at most `concurrency` at once.
+    pub async fn warm(&self, btags: &[Battletag], concurrency: usize) -> WarmReport {
+        let results: Vec<_> = futures::stream::iter(btags.iter().cloned())
+            .map(|btag| async move {
+                let result = self.profile_full(&btag).await;
+                (btag, result)
+            })
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await;
+        let mut report = WarmReport::default();
+        for (battletag, result) in results {
+            match result {
+                Ok(_) => report.loaded.push(battletag),
+                Err(error) => report.failed.push(WarmFailure {
+                    battletag,
+                    error: error.to_string(),
+                }),
             }
         }
-        let search = self.client.search(name).await?;
-        self.search_cache
-            .lock()
-            .cache_set(name.to_owned(), search.clone());
-        Ok(search)
+        report
     }
 
-    pub const fn assets(&self) -> &HashMap<Id, Asset> {
-        self.client.assets()
+    /// Fetches the requested fields of every player, at most `concurrency` players at once.
+    /// Failures are reported per player and field. The response is as fresh as the oldest
+    /// field that could be fetched.
+    pub async fn batch(&self, request: &BatchRequest, concurrency: usize) -> Cached<BatchResponse> {
+        let fields = request.fields();
+        let parts = request.parts();
+        let mut btags = Vec::new();
+        for btag in &request.battletags {
+            if !btags.contains(btag) {
+                btags.push(btag.clone());
+            }
+        }
+        let items: Vec<_> = futures::stream::iter(btags)
+            .map(|btag| {
+                let fields = &fields;
+                async move {
+                    let item = self.batch_item(&btag, fields, parts).await;
+                    (btag.to_string(), item)
+                }
+            })
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await;
+        let mut cached = Cached::unit();
+        let mut players = HashMap::new();
+        for (btag, item) in items {
+            cached = cached.merge(&item);
+            players.insert(btag, item.value);
+        }
+        cached.map(|()| BatchResponse { players })
     }
 
-    pub fn heroes(&self) -> &[Hero] {
-        self.client.heroes()
+    async fn batch_item(
+        &self,
+        btag: &Battletag,
+        fields: &[BatchField],
+        parts: ProfileParts,
+    ) -> Cached<BatchItem> {
+        let profile = async { Ok(self.profile_parts(btag, parts).await?.map(|p| (*p).clone())) };
+        let overbuff = async { Ok(self.overbuff(btag).await?.map(|o| (*o).clone())) };
+        let summary = self.summary(btag, Platform::Pc, Mode::Competitive);
+        let (found, profile, overbuff, summary) = futures::join!(
+            batch_field(fields, BatchField::Found, self.found(btag)),
+            batch_field(fields, BatchField::Profile, profile),
+            batch_field(fields, BatchField::Overbuff, overbuff),
+            batch_field(fields, BatchField::Summary, summary),
+        );
+
+        let mut cached = Cached::unit();
+        let mut errors = Vec::new();
+        let item = BatchItem {
+            found: batch_result(&mut cached, &mut errors, BatchField::Found, found),
+            profile: batch_result(&mut cached, &mut errors, BatchField::Profile, profile),
+            overbuff: batch_result(&mut cached, &mut errors, BatchField::Overbuff, overbuff),
+            summary: batch_result(&mut cached, &mut errors, BatchField::Summary, summary),
+            errors,
+        };
+        cached.map(|()| item)
+    }
+
+    /// Whether the career page of a cached profile has not been updated since it was fetched,
+    /// going by the cached search result. A stale search result proves nothing.
+    async fn unchanged(&self, profile: &PlayerProfile) -> bool {
+        match self.found(&profile.battletag).await {
+            Ok(found) => !found.stale && found.value.last_updated <= profile.last_updated,
+            Err(error) => {
+                tracing::warn!(btag = ?profile.battletag, %error, "Could not check last update");
+                false
+            }
+        }
+    }
+
+    fn evict(&self, key: &CacheKey) -> bool {
+        let removed = self.store.invalidate(key);
+        if removed {
+            self.metrics.get(key.kind).evict();
+        }
+        removed
+    }
+
+    fn is_stale(&self, kind: CacheKind, age: Duration) -> bool {
+        self.soft.is_some_and(|(soft, _)| age >= soft.get(kind))
+    }
+
+    /// Time until a value of `age` turns stale, or expires if there is no soft TTL.
+    fn max_age(&self, kind: CacheKind, age: Duration) -> Duration {
+        let ttl = self
+            .soft
+            .map_or_else(|| self.store.lifespan(kind), |(soft, _)| soft.get(kind));
+        ttl.saturating_sub(age)
+    }
+
+    async fn cached<V, F, Fut>(&self, key: CacheKey, fetch: F) -> crate::Result<Cached<Arc<V>>>
+    where
+        V: CacheValue,
+        F: FnOnce(Arc<Client>, Option<Arc<V>>) -> Fut,
+        Fut: Future<Output = crate::Result<Arc<V>>> + Send + 'static,
+    {
+        let kind = key.kind;
+        let cached = self.lookup(key, fetch).await?;
+        Ok(Cached {
+            max_age: self.max_age(kind, cached.age),
+            ..cached
+        })
+    }
+
+    /// `fetch` receives the stale value when revalidating.
+    async fn lookup<V, F, Fut>(&self, key: CacheKey, fetch: F) -> crate::Result<Cached<Arc<V>>>
+    where
+        V: CacheValue,
+        F: FnOnce(Arc<Client>, Option<Arc<V>>) -> Fut,
+        Fut: Future<Output = crate::Result<Arc<V>>> + Send + 'static,
+    {
+        let mut finished = self.flights.finished.load(Ordering::Acquire);
+        let Some(entry) = self.store.get::<V>(&key) else {
+            self.metrics.get(key.kind).miss();
+            let flight = loop {
+                let mut flights = self.flights.running.lock();
+                if let Some(flight) = self.running(&flights, &key) {
+                    break flight;
+                }
+                let now_finished = self.flights.finished.load(Ordering::Acquire);
+                if now_finished == finished {
+                    break self.start(&mut flights, key, |client| fetch(client, None));
+                }
+                // a flight that finished since the store was read may have filled it
+                drop(flights);
+                if let Some(entry) = self.store.get(&key) {
+                    return Ok(entry.into());
+                }
+                finished = now_finished;
+            };
+            return Ok(flight.await.map_err(Error::Shared)?.into());
+        };
+
+        self.metrics.get(key.kind).hit();
+        let cached = Cached::from(entry);
+        let Some((_, revalidate)) = self.soft else {
+            return Ok(cached);
+        };
+        if !self.is_stale(key.kind, cached.age) {
+            return Ok(cached);
+        }
+        let stale = Cached {
+            stale: true,
+            ..cached
+        };
+        let (flight, started) = {
+            let mut flights = self.flights.running.lock();
+            match self.running(&flights, &key) {
+                Some(flight) => (flight, false),
+                None => {
+                    let previous = stale.value.clone();
+                    let fetch = |client| fetch(client, Some(previous));
+                    (self.start(&mut flights, key.clone(), fetch), true)
+                }
+            }
+        };
+        match revalidate {
+            Revalidate::Background => {
+                self.metrics.get(key.kind).serve_stale();
+                if started {
+                    tokio::spawn(async move {
+                        if let Err(error) = flight.await {
+                            tracing::warn!(?key, %error, "Background refresh failed");
+                        }
+                    });
+                }
+                Ok(stale)
+            }
+            Revalidate::OnError => match flight.await {
+                Ok(value) => Ok(value.into()),
+                Err(error) => {
+                    tracing::warn!(?key, %error, age = ?stale.age, "Serving stale value after upstream error");
+                    self.metrics.get(key.kind).serve_stale();
+                    Ok(stale)
+                }
+            },
+        }
+    }
+
+    fn running<V: CacheValue>(&self, flights: &Running, key: &CacheKey) -> Option<Flight<V>> {
+        let flight = flights.get(key)?.downcast_ref::<Flight<V>>()?;
+        self.metrics.get(key.kind).coalesce();
+        Some(flight.clone())
+    }
+
+    fn start<V, F, Fut>(&self, flights: &mut Running, key: CacheKey, fetch: F) -> Flight<V>
+    where
+        V: CacheValue,
+        F: FnOnce(Arc<Client>) -> Fut,
+        Fut: Future<Output = crate::Result<Arc<V>>> + Send + 'static,
+    {
+        self.metrics.get(key.kind).fetch();
+        let flight = self.flight(key.clone(), fetch(self.client.clone()));
+        flights.insert(key, Box::new(flight.clone()));
+        flight
+    }
+
+    fn flight<V: CacheValue>(
+        &self,
+        key: CacheKey,
+        fetch: impl Future<Output = crate::Result<Arc<V>>> + Send + 'static,
+    ) -> Flight<V> {
+        let store = self.store.clone();
+        let flights = self.flights.clone();
+        let metrics = self.metrics.clone();
+        async move {
+            let result = fetch.await;
+            if let Ok(value) = &result {
+                if let Some(evicted) = store.set(key.clone(), value.clone()) {
+                    metrics.get(evicted.kind).evict();
+                }
+            }
+            let mut running = flights.running.lock();
+            running.remove(&key);
+            flights.finished.fetch_add(1, Ordering::Release);
+            drop(running);
+            result.map_err(Arc::new)
+        }
+        .boxed()
+        .shared()
+    }
+}
+
+/// Runs `fetch` only if `field` was requested.
+async fn batch_field<T>(
+    fields: &[BatchField],
+    field: BatchField,
+    fetch: impl Future<Output = crate::Result<T>>,
+) -> Option<crate::Result<T>> {
+    if fields.contains(&field) {
+        Some(fetch.await)
+    } else {
+        None
+    }
+}
+
+/// The value of a requested field, whose freshness is merged into `cached`, or `None` after
+/// adding its error to `errors`.
+fn batch_result<T>(
+    cached: &mut Cached<()>,
+    errors: &mut Vec<BatchError>,
+    field: BatchField,
+    result: Option<crate::Result<Cached<T>>>,
+) -> Option<T> {
+    match result? {
+        Ok(value) => {
+            *cached = cached.clone().merge(&value);
+            Some(value.value)
+        }
+        Err(error) => {
+            errors.push(BatchError {
+                field,
+                error: error.to_string(),
+            });
+            None
+        }
+    }
+}
+
+impl<T> Cached<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Cached<U> {
+        Cached {
+            value: f(self.value),
+            age: self.age,
+            stale: self.stale,
+            max_age: self.max_age,
+            fetches: self.fetches,
+        }
+    }
+
+    /// Fetched upstream for this call rather than served from the store.
+    pub fn fetched(&self) -> bool {
+        self.fetches > 0
+    }
+
+    /// Combines the freshness of `other`, which the value is partly derived from: the older
+    /// age and shorter max age of the two, stale if either of them is, and the fetches of both.
+    #[must_use]
+    pub fn merge<U>(self, other: &Cached<U>) -> Self {
+        Self {
+            value: self.value,
+            age: self.age.max(other.age),
+            stale: self.stale || other.stale,
+            max_age: self.max_age.min(other.max_age),
+            fetches: self.fetches + other.fetches,
+        }
+    }
+}
+
+impl Cached<()> {
+    /// Freshness of a value derived from nothing yet, to [`merge`](Cached::merge) the values
+    /// it is derived from into.
+    pub(crate) fn unit() -> Self {
+        Self {
+            value: (),
+            age: Duration::ZERO,
+            stale: false,
+            max_age: Duration::MAX,
+            fetches: 0,
+        }
+    }
+}
+
+impl<T> From<T> for Cached<T> {
+    fn from(value: T) -> Self {
+        Self {
+            value,
+            age: Duration::ZERO,
+            stale: false,
+            max_age: Duration::ZERO,
+            fetches: 1,
+        }
+    }
+}
+
+impl<T> From<CacheEntry<T>> for Cached<T> {
+    fn from(entry: CacheEntry<T>) -> Self {
+        Self {
+            age: entry.age(),
+            value: entry.value,
+            stale: false,
+            max_age: Duration::ZERO,
+            fetches: 0,
+        }
+    }
+}
+
+// not derived, which would require `S: Clone`
+impl<S> Clone for CachedClient<S> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            store: self.store.clone(),
+            flights: self.flights.clone(),
+            metrics: self.metrics.clone(),
+            soft: self.soft,
+            profile_expiry: self.profile_expiry,
+        }
+    }
+}
+
+impl<S: std::fmt::Debug> std::fmt::Debug for CachedClient<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CachedClient")
+            .field("client", &self.client)
+            .field("store", &self.store)
+            .field("flights", &self.flights.running.lock().len())
+            .field("metrics", &self.metrics)
+            .field("soft", &self.soft)
+            .field("profile_expiry", &self.profile_expiry)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU32;
+
+    use super::*;
+    use crate::Upstream;
+
+    fn client(lifespan: u64) -> CachedClient {
+        let store = LruStore::new(Lifespans::new(lifespan, lifespan, lifespan), 16);
+        CachedClient::with_client(Client::unfetched(Upstream::default()), store)
+    }
+
+    /// Looks up `key`, fetching the next value of `fetches` after `delay_ms` or failing if
+    /// `fail` is set.
+    async fn lookup(
+        client: &CachedClient,
+        key: &str,
+        fetches: &Arc<AtomicU32>,
+        delay_ms: u64,
+        fail: bool,
+    ) -> crate::Result<Cached<Arc<u32>>> {
+        let fetches = fetches.clone();
+        client
+            .cached(CacheKey::search(key), move |_, _| async move {
+                let n = fetches.fetch_add(1, Ordering::SeqCst) + 1;
+                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                if fail {
+                    Err(Error::Http(StatusCode::INTERNAL_SERVER_ERROR))
+                } else {
+                    Ok(Arc::new(n))
+                }
+            })
+            .await
+    }
+
+    #[test]
+    fn merged_values_are_as_fresh_as_the_oldest() {
+        let old = Cached {
+            value: (),
+            age: Duration::from_secs(30),
+            stale: false,
+            max_age: Duration::from_secs(10),
+            fetches: 0,
+        };
+        let fetched = Cached::from(2);
+        let merged = Cached::unit().merge(&old).merge(&fetched).map(|()| 1);
+        assert_eq!(merged.value, 1);
+        assert_eq!(merged.age, Duration::from_secs(30));
+        assert_eq!(merged.max_age, Duration::ZERO);
+        assert!(merged.fetched() && !merged.stale);
+
+        let merged = Cached::unit().merge(&old);
+        assert_eq!(merged.max_age, Duration::from_secs(10));
+        assert_eq!(merged.fetches, 0);
+    }
+
+    #[tokio::test]
+    async fn concurrent_lookups_share_one_fetch() {
+        let client = client(60);
+        let fetches = Arc::new(AtomicU32::new(0));
+        let (a, b, c) = futures::join!(
+            lookup(&client, "a", &fetches, 50, false),
+            lookup(&client, "a", &fetches, 50, false),
+            lookup(&client, "a", &fetches, 50, false),
+        );
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        for cached in [a, b, c] {
+            assert_eq!(*cached.unwrap().value, 1);
+        }
+        let metrics = client.metrics().search;
+        assert_eq!(
+            (metrics.fetches, metrics.coalesced, metrics.misses),
+            (1, 2, 3)
+        );
+
+        let cached = lookup(&client, "a", &fetches, 0, false).await.unwrap();
+        assert!(!cached.fetched());
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn different_keys_are_fetched_separately() {
+        let client = client(60);
+        let fetches = Arc::new(AtomicU32::new(0));
+        let (a, b) = futures::join!(
+            lookup(&client, "a", &fetches, 50, false),
+            lookup(&client, "b", &fetches, 50, false),
+        );
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+        assert_ne!(a.unwrap().value, b.unwrap().value);
+    }
+
+    #[tokio::test]
+    async fn shared_failures_are_not_cached() {
+        let client = client(60);
+        let fetches = Arc::new(AtomicU32::new(0));
+        let (a, b) = futures::join!(
+            lookup(&client, "a", &fetches, 50, true),
+            lookup(&client, "a", &fetches, 50, true),
+        );
+        assert!(matches!(a, Err(Error::Shared(_))));
+        assert!(matches!(b, Err(Error::Shared(_))));
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        let cached = lookup(&client, "a", &fetches, 0, false).await.unwrap();
+        assert!(cached.fetched());
+        assert_eq!(*cached.value, 2);
+    }
+
+    #[tokio::test]
+    async fn entries_expire_after_the_hard_ttl() {
+        let client = client(0);
+        let fetches = Arc::new(AtomicU32::new(0));
+        lookup(&client, "a", &fetches, 0, false).await.unwrap();
+        let cached = lookup(&client, "a", &fetches, 0, false).await.unwrap();
+        assert!(cached.fetched());
+        assert_eq!(*cached.value, 2);
+    }
+
+    #[tokio::test]
+    async fn fresh_entries_are_served_until_the_soft_ttl() {
+        let client = client(60).stale_if_error(Lifespans::new(30, 30, 30));
+        let fetches = Arc::new(AtomicU32::new(0));
+        let fetched = lookup(&client, "a", &fetches, 0, false).await.unwrap();
+        assert_eq!(fetched.max_age, Duration::from_secs(30));
+        let cached = lookup(&client, "a", &fetches, 0, false).await.unwrap();
+        assert!(!cached.fetched() && !cached.stale);
+        assert!(cached.max_age <= Duration::from_secs(30));
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn stale_while_revalidate_refreshes_in_the_background() {
+        let client = client(60).stale_while_revalidate(Lifespans::new(0, 0, 0));
+        let fetches = Arc::new(AtomicU32::new(0));
+        lookup(&client, "a", &fetches, 0, false).await.unwrap();
+
+        let stale = lookup(&client, "a", &fetches, 20, false).await.unwrap();
+        assert!(stale.stale && !stale.fetched());
+        assert_eq!(*stale.value, 1);
+        assert_eq!(stale.max_age, Duration::ZERO);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+
+        let refreshed = lookup(&client, "a", &fetches, 20, false).await.unwrap();
+        assert_eq!(*refreshed.value, 2);
+        assert_eq!(client.metrics().search.stale, 2);
+    }
+
+    #[tokio::test]
+    async fn stale_if_error_serves_stale_values_only_on_failure() {
+        let client = client(60).stale_if_error(Lifespans::new(0, 0, 0));
+        let fetches = Arc::new(AtomicU32::new(0));
+        lookup(&client, "a", &fetches, 0, false).await.unwrap();
+
+        let stale = lookup(&client, "a", &fetches, 0, true).await.unwrap();
+        assert!(stale.stale);
+        assert_eq!(*stale.value, 1);
+
+        let refreshed = lookup(&client, "a", &fetches, 0, false).await.unwrap();
+        assert!(refreshed.fetched() && !refreshed.stale);
+        assert_eq!(*refreshed.value, 3);
+    }
+
+    #[tokio::test]
+    async fn failures_are_returned_without_a_stale_value() {
+        let client = client(0).stale_if_error(Lifespans::new(0, 0, 0));
+        let fetches = Arc::new(AtomicU32::new(0));
+        lookup(&client, "a", &fetches, 0, false).await.unwrap();
+        assert!(lookup(&client, "a", &fetches, 0, true).await.is_err());
+    }
+
+    /// A client with the full profile parsed from a fixture and a search result reporting
+    /// `since` after the profile was last updated cached, whose profiles are stale at once and
+    /// fail to fetch.
+    fn expiring_client(since: chrono::Duration) -> (CachedClient, Arc<PlayerProfile>) {
+        let btag = Battletag::new("Player", 1234);
+        let html = include_str!("../fixtures/career/public.html");
+        let profile =
+            crate::parse_profile(&btag, html, ProfileParts::ALL, &Default::default()).unwrap();
+        let profile = Arc::new(profile);
+        let found = FoundPlayer {
+            battle_tag: btag.clone(),
+            last_updated: profile.last_updated + since,
+            is_public: true,
+            namecard: None,
+            portrait: None,
+            title: None,
+        };
+        let store = LruStore::new(Lifespans::new(60, 60, 60), 16);
+        store.set(CacheKey::search(&btag.name), Arc::new(vec![found]));
+        store.set(CacheKey::profile(&btag, ProfileParts::ALL), profile.clone());
+        let client = Client::unfetched(Upstream::mirror("http://127.0.0.1:1"));
+        let client = CachedClient::with_client(client, store)
+            .stale_if_error(Lifespans::new(0, 60, 60))
+            .profile_expiry(ProfileExpiry::LastUpdated);
+        (client, profile)
+    }
+
+    #[tokio::test]
+    async fn unchanged_profiles_are_revalidated_from_the_search_result() {
+        let (client, profile) = expiring_client(chrono::Duration::zero());
+        let cached = client.profile_full(&profile.battletag).await.unwrap();
+        assert!(Arc::ptr_eq(&cached.value, &profile));
+        assert!(!cached.stale);
+        let metrics = client.metrics();
+        assert_eq!(
+            (metrics.profile.revalidated, metrics.search.fetches),
+            (1, 0)
+        );
+    }
+
+    #[tokio::test]
+    async fn updated_profiles_are_refetched() {
+        let (client, profile) = expiring_client(chrono::Duration::days(1));
+        let cached = client.profile_full(&profile.battletag).await.unwrap();
+        // the refetch fails, so the stale profile is served
+        assert!(cached.stale);
+        let metrics = client.metrics();
+        assert_eq!(
+            (metrics.profile.revalidated, metrics.profile.fetches),
+            (0, 1)
+        );
+        assert_eq!(metrics.search.fetches, 0);
+    }
+
+    /// Serves `html` on every request to the returned base URL, counting the requests.
+    fn serve(html: &'static str) -> (String, Arc<AtomicU32>) {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicU32::new(0));
+        let counter = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+                    line.clear();
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    html.len()
+                );
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(html.as_bytes());
+            }
+        });
+        (base, requests)
+    }
+
+    /// Caches the profile of `btag` parsed from a fixture with `parts`.
+    fn set_profile(client: &CachedClient, btag: &Battletag, parts: ProfileParts) -> CacheKey {
+        let html = include_str!("../fixtures/career/public.html");
+        let profile = crate::parse_profile(btag, html, parts, &Default::default()).unwrap();
+        let key = CacheKey::profile(btag, parts);
+        client.store.set(key.clone(), Arc::new(profile));
+        key
+    }
+
+    #[test]
+    fn evict_player_removes_only_entries_of_the_player() {
+        let client = client(60);
+        let btag = Battletag::new("Player", 1234);
+        let other = Battletag::new("Player", 12345);
+        let full = set_profile(&client, &btag, ProfileParts::ALL);
+        let reduced = set_profile(&client, &btag, ProfileParts::REDUCED);
+        let other = set_profile(&client, &other, ProfileParts::ALL);
+        let overbuff = CacheKey::overbuff(&btag);
+        let search = CacheKey::search(&btag.name);
+        client.store.set(overbuff.clone(), Arc::new(1));
+        client.store.set(search.clone(), Arc::new(2));
+
+        assert!(client.evict_player(&btag));
+        let mut keys = client.store.keys();
+        keys.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(keys, [search, other]);
+        assert!(client.store.get::<u32>(&overbuff).is_none());
+        for key in [full, reduced] {
+            assert!(client.store.get::<PlayerProfile>(&key).is_none());
+        }
+        let metrics = client.metrics();
+        assert_eq!(
+            (metrics.profile.evictions, metrics.overbuff.evictions),
+            (2, 1)
+        );
+        assert!(!client.evict_player(&btag));
+    }
+
+    #[tokio::test]
+    async fn refresh_profile_replaces_the_cached_profiles() {
+        let (base, requests) = serve(include_str!("../fixtures/career/public.html"));
+        let store = LruStore::new(Lifespans::new(60, 60, 60), 16);
+        let client = CachedClient::with_client(Client::unfetched(Upstream::mirror(&base)), store);
+        let btag = Battletag::new("Player", 1234);
+        let full = set_profile(&client, &btag, ProfileParts::ALL);
+        let reduced = set_profile(&client, &btag, ProfileParts::REDUCED);
+        let cached = client.store.get::<PlayerProfile>(&full).unwrap();
+
+        let refreshed = client.refresh_profile(&btag).await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert!(!Arc::ptr_eq(&refreshed, &cached.value));
+        let entry = client.store.get::<PlayerProfile>(&full).unwrap();
+        assert!(Arc::ptr_eq(&entry.value, &refreshed));
+        assert!(entry.inserted > cached.inserted);
+        assert!(client.store.get::<PlayerProfile>(&reduced).is_none());
+    }
+
+    #[tokio::test]
+    async fn refresh_profile_keeps_the_full_profile_on_failure() {
+        let store = LruStore::new(Lifespans::new(60, 60, 60), 16);
+        let upstream = Upstream::mirror("http://127.0.0.1:1");
+        let client = CachedClient::with_client(Client::unfetched(upstream), store);
+        let btag = Battletag::new("Player", 1234);
+        let full = set_profile(&client, &btag, ProfileParts::ALL);
+        assert!(client.refresh_profile(&btag).await.is_err());
+        assert!(client.store.get::<PlayerProfile>(&full).is_some());
+    }
+
+    #[tokio::test]
+    async fn warm_reports_loaded_and_failed_players() {
+        let (base, requests) = serve("<html></html>");
+        let store = LruStore::new(Lifespans::new(60, 60, 60), 16);
+        let client = CachedClient::with_client(Client::unfetched(Upstream::mirror(&base)), store);
+        let cached = Battletag::new("Player", 1234);
+        let unparsable = Battletag::new("Player", 5678);
+        set_profile(&client, &cached, ProfileParts::ALL);
+
+        let btags = [cached.clone(), unparsable.clone()];
+        let report = client.warm(&btags, 0).await;
+        assert_eq!(report.loaded, [cached]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].battletag, unparsable);
+        assert!(!report.failed[0].error.is_empty());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
     }
 }
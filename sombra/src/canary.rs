@@ -0,0 +1,245 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use serde_derive::Deserialize;
+use sombra_types::{
+    Battletag, Drift, DriftReport, PageKind, PageReport, PageShape, PlayerProfile, ProfileParts,
+};
+
+use crate::{heroes::parse_heroes, parse_overbuff, parse_profile, Client, Error, Selectors};
+
+/// File in the fixture directory that lists the canary pages.
+pub const CANARY_FILE: &str = "canary.toml";
+
+/// Corpus of pages that every parser is run against to notice upstream markup changes before
+/// players do. Recorded pages are read from the fixture directory, fresh ones are fetched.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Canary {
+    #[serde(skip)]
+    dir: PathBuf,
+    #[serde(default, rename = "page")]
+    pub pages: Vec<CanaryPage>,
+}
+
+/// A recorded page, or one fetched for every run, with what its parser should extract from it.
+/// Unset expectations are not checked.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CanaryPage {
+    pub kind: PageKind,
+    /// Recorded page, relative to the fixture directory. Also reported as the page name.
+    pub file: Option<PathBuf>,
+    /// Player whose page is fetched, as `Name#1234`. Ignored for the hero overview.
+    pub battletag: Option<String>,
+    /// Fetch the page on fresh runs instead of reading `file`
+    #[serde(default)]
+    pub fresh: bool,
+    pub private: Option<bool>,
+    pub min_ranks: Option<u64>,
+    pub min_heroes: Option<u64>,
+    pub min_stats: Option<u64>,
+}
+
+impl Canary {
+    /// Reads [`CANARY_FILE`] from the fixture directory `dir`.
+    pub fn load(dir: impl Into<PathBuf>) -> crate::Result<Self> {
+        let dir = dir.into();
+        let toml = fs::read_to_string(dir.join(CANARY_FILE))?;
+        let mut canary: Self =
+            toml::from_str(&toml).map_err(|e| Error::Canary(format!("{CANARY_FILE}: {e}")))?;
+        canary.dir = dir;
+        Ok(canary)
+    }
+
+    /// The fixture directory the canary was loaded from.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Checks the recorded pages only.
+    pub fn check_recorded(&self, selectors: &Selectors) -> DriftReport {
+        let pages = self
+            .pages
+            .iter()
+            .filter(|page| !page.fresh)
+            .map(|page| self.check_file(page, selectors))
+            .collect();
+        report(selectors, pages)
+    }
+
+    /// Checks the recorded pages, and the fresh ones if `fresh` is set, with the selectors of
+    /// `client`.
+    pub async fn check(&self, client: &Client, fresh: bool) -> DriftReport {
+        let selectors = client.selectors();
+        let mut pages = Vec::new();
+        for page in &self.pages {
+            if !page.fresh {
+                pages.push(self.check_file(page, &selectors));
+            } else if fresh {
+                pages.push(check_fresh(client, page, &selectors).await);
+            }
+        }
+        report(&selectors, pages)
+    }
+
+    fn check_file(&self, page: &CanaryPage, selectors: &Selectors) -> PageReport {
+        let name = page
+            .file
+            .as_deref()
+            .map_or_else(String::new, |file| file.display().to_string());
+        let html = match &page.file {
+            Some(file) => fs::read_to_string(self.dir.join(file)).map_err(Error::from),
+            None => Err(Error::Canary("Recorded page without file".to_owned())),
+        };
+        let shape = html.and_then(|html| shape(page, &html, selectors));
+        page_report(page, name, false, shape)
+    }
+}
+
+async fn check_fresh(client: &Client, page: &CanaryPage, selectors: &Selectors) -> PageReport {
+    let btag = page.battletag.as_deref().map(str::parse::<Battletag>);
+    let (name, html) = match (page.kind, btag) {
+        (PageKind::Heroes, _) => ("heroes".to_owned(), client.heroes_page().await),
+        (_, Some(Ok(btag))) => {
+            let html = match page.kind {
+                PageKind::Career => client.career_page(&btag).await,
+                _ => client.overbuff_page(&btag).await,
+            };
+            (btag.to_string(), html)
+        }
+        _ => {
+            let name = page.battletag.clone().unwrap_or_default();
+            (name.clone(), Err(Error::Battletag(name)))
+        }
+    };
+    let shape = html.and_then(|html| shape(page, &html, selectors));
+    page_report(page, name, true, shape)
+}
+
+fn shape(page: &CanaryPage, html: &str, selectors: &Selectors) -> crate::Result<PageShape> {
+    let btag = Battletag::new("Canary", 1);
+    Ok(match page.kind {
+        PageKind::Career => {
+            profile_shape(&parse_profile(&btag, html, ProfileParts::ALL, selectors)?)
+        }
+        PageKind::Overbuff => PageShape {
+            ranks: parse_overbuff(html, selectors)?.len() as u64,
+            ..PageShape::default()
+        },
+        PageKind::Heroes => PageShape {
+            heroes: parse_heroes(html, selectors)?.len() as u64,
+            ..PageShape::default()
+        },
+    })
+}
+
+fn profile_shape(profile: &PlayerProfile) -> PageShape {
+    let heroes: HashSet<_> = profile.tables().flat_map(|t| t.keys()).collect();
+    let stats: HashSet<_> = profile
+        .tables()
+        .flat_map(|t| t.values())
+        .flat_map(|hero| hero.stats.keys())
+        .collect();
+    PageShape {
+        private: profile.visibility.is_private(),
+        ranks: profile.ranks.len() as u64,
+        heroes: heroes.len() as u64,
+        stats: stats.len() as u64,
+    }
+}
+
+fn page_report(
+    page: &CanaryPage,
+    name: String,
+    fresh: bool,
+    shape: crate::Result<PageShape>,
+) -> PageReport {
+    let (shape, error) = match shape {
+        Ok(shape) => (Some(shape), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    PageReport {
+        kind: page.kind,
+        page: name,
+        fresh,
+        drift: shape.map(|shape| drift(page, shape)).unwrap_or_default(),
+        shape,
+        error,
+    }
+}
+
+fn drift(page: &CanaryPage, shape: PageShape) -> Vec<Drift> {
+    let mut drift = Vec::new();
+    if let Some(private) = page.private.filter(|p| *p != shape.private) {
+        drift.push(Drift {
+            field: "private".to_owned(),
+            expected: private.to_string(),
+            actual: shape.private.to_string(),
+        });
+    }
+    for (field, min, actual) in [
+        ("ranks", page.min_ranks, shape.ranks),
+        ("heroes", page.min_heroes, shape.heroes),
+        ("stats", page.min_stats, shape.stats),
+    ] {
+        if let Some(min) = min.filter(|min| actual < *min) {
+            drift.push(Drift {
+                field: field.to_owned(),
+                expected: format!(">= {min}"),
+                actual: actual.to_string(),
+            });
+        }
+    }
+    drift
+}
+
+fn report(selectors: &Selectors, pages: Vec<PageReport>) -> DriftReport {
+    DriftReport {
+        checked_at: Utc::now(),
+        selectors_version: selectors.version,
+        drifted: pages
+            .iter()
+            .any(|page| page.error.is_some() || !page.drift.is_empty()),
+        pages,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn recorded_pages_match_their_expectations() {
+        let canary = Canary::load(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures")).unwrap();
+        let report = canary.check_recorded(&Selectors::default());
+        assert!(!report.drifted, "{report:#?}");
+        let kinds: HashSet<_> = report.pages.iter().map(|page| page.kind).collect();
+        assert_eq!(kinds.len(), 3);
+    }
+
+    #[test]
+    fn outdated_selectors_are_reported_as_drift() {
+        let canary = Canary::load(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures")).unwrap();
+        let mut selectors = Selectors::default();
+        selectors.overbuff.rank_container_index = 2;
+        selectors.heroes.card = ".heroTile".to_owned();
+        let report = canary.check_recorded(&selectors);
+        assert!(report.drifted);
+
+        let pages: HashMap<_, _> = report
+            .pages
+            .iter()
+            .map(|page| (page.page.as_str(), page))
+            .collect();
+        assert!(pages["overbuff/public.html"].error.is_some());
+        let heroes = &pages["heroes/index.html"];
+        assert_eq!(heroes.drift.len(), 1);
+        assert_eq!(heroes.drift[0].field, "heroes");
+        assert!(pages["career/public.html"].drift.is_empty());
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use reqwest::StatusCode;
 use thiserror::Error;
 
@@ -13,10 +15,24 @@ pub enum Error {
     Battletag(String),
     #[error("HTTP error: {0}")]
     Http(StatusCode),
+    /// Upstream responded with 429, with the seconds from its `Retry-After` header
+    #[error("Rate limited by upstream")]
+    RateLimited(Option<u64>),
     #[error("HTML parsing error: {0}")]
     Html(#[from] tl::ParseError),
     #[error("Profile parsing error")]
     Parse,
+    #[error("Invalid selectors: {}", .0.join("; "))]
+    Selectors(Vec<String>),
+    #[error("Invalid canary: {0}")]
+    Canary(String),
+    #[error("Unknown data source: {0}")]
+    UnknownSource(String),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Error of a request that was shared between concurrent lookups
+    #[error(transparent)]
+    Shared(Arc<Error>),
 }
 
 impl Error {
@@ -33,6 +49,34 @@ impl Error {
         }
     }
 
+    /// Returns the original error behind any [`Error::Shared`] wrappers.
+    #[must_use]
+    pub fn root(&self) -> &Self {
+        match self {
+            Self::Shared(e) => e.root(),
+            e => e,
+        }
+    }
+
+    /// Name of the variant of the [root](Self::root) error.
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Request(_) => "request",
+            Self::Deserializer(_) => "deserializer",
+            Self::Battletag(_) => "battletag",
+            Self::Http(_) => "http",
+            Self::RateLimited(_) => "rateLimited",
+            Self::Html(_) => "html",
+            Self::Parse => "parse",
+            Self::Selectors(_) => "selectors",
+            Self::Canary(_) => "canary",
+            Self::UnknownSource(_) => "unknownSource",
+            Self::Io(_) => "io",
+            Self::Shared(e) => e.code(),
+        }
+    }
+
     #[must_use]
     pub fn parse() -> Self {
         let backtrace = std::backtrace::Backtrace::force_capture();
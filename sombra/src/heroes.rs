@@ -1,66 +1,82 @@
 use std::borrow::Borrow;
 
-use sombra_types::{Color, Hero, Role};
+use sombra_types::{Color, Hero, PageKind};
 use tl::ParserOptions;
 use tracing::instrument;
 
 use crate::{
+    quarantine::Page,
     util::{find_all, find_attr2},
-    Client, Error,
+    Client, Error, Selectors,
 };
 
 impl Client {
     #[instrument(level = "debug", skip(self))]
     pub async fn fetch_heroes(&mut self) -> crate::Result<()> {
-        let mut heroes = Vec::new();
-        let html = self
-            .get("https://overwatch.blizzard.com/en-us/heroes/")
-            .await?;
-        let dom = tl::parse(&html, ParserOptions::new())?;
-
-        for card in find_all(&dom, ".heroCard") {
-            let portrait = find_attr2(&dom, card, ".heroCardPortrait", "src")
-                .ok_or_else(Error::parse)?
-                .parse()
-                .map_err(|_| Error::parse())?;
-            let role_str = card
-                .attributes()
-                .get("data-role")
-                .flatten()
-                .ok_or_else(Error::parse)?
-                .as_utf8_str();
-            let role = match role_str.borrow() {
-                "tank" => Role::Tank,
-                "damage" => Role::Damage,
-                "support" => Role::Support,
-                _ => return Err(Error::parse()),
-            };
-            let name = card
-                .attributes()
-                .get("hero-name")
-                .flatten()
-                .ok_or_else(Error::parse)?
-                .as_utf8_str()
-                .to_string();
-
-            heroes.push(Hero {
-                color: hero_color(&name),
-                name,
-                role,
-                portrait,
-            });
-        }
-
-        self.heroes = heroes;
+        let url = self.upstream.heroes_url();
+        let html = self.get(&url).await?;
+        let page = Page {
+            kind: PageKind::Heroes,
+            url: &url,
+            btag: None,
+            parts: None,
+        };
+        self.heroes =
+            self.parse_page(&page, &html, |html| parse_heroes(html, &self.selectors()))?;
         Ok(())
     }
 
+    /// The unparsed hero overview page.
+    pub async fn heroes_page(&self) -> crate::Result<String> {
+        self.get(&self.upstream.heroes_url()).await
+    }
+
     #[must_use]
     pub fn heroes(&self) -> &[Hero] {
         &self.heroes
     }
 }
 
+/// Parses the hero cards of the hero overview page.
+pub fn parse_heroes(html: &str, selectors: &Selectors) -> crate::Result<Vec<Hero>> {
+    let selectors = &selectors.heroes;
+    let mut heroes = Vec::new();
+    let dom = tl::parse(html, ParserOptions::new())?;
+
+    for card in find_all(&dom, &selectors.card) {
+        let portrait = find_attr2(&dom, card, &selectors.portrait, &selectors.portrait_attr)
+            .ok_or_else(Error::parse)?
+            .parse()
+            .map_err(|_| Error::parse())?;
+        let role_str = card
+            .attributes()
+            .get(selectors.role_attr.as_str())
+            .flatten()
+            .ok_or_else(Error::parse)?
+            .as_utf8_str();
+        let role = *selectors
+            .roles
+            .get(role_str.borrow() as &str)
+            .ok_or_else(Error::parse)?;
+        let name = card
+            .attributes()
+            .get(selectors.name_attr.as_str())
+            .flatten()
+            .ok_or_else(Error::parse)?
+            .as_utf8_str()
+            .to_string();
+
+        heroes.push(Hero {
+            color: hero_color(&name),
+            name,
+            role,
+            portrait,
+        });
+    }
+
+    Ok(heroes)
+}
+
 #[allow(clippy::non_ascii_literal)]
 #[allow(clippy::match_same_arms)]
 pub fn hero_color(hero: &str) -> Color {
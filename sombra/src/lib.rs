@@ -4,52 +4,130 @@
 
 mod assets;
 mod cached;
+mod canary;
 mod error;
 mod heroes;
+mod limit;
+mod metrics;
 mod overbuff;
 mod profile;
+mod quarantine;
+mod ranks;
+mod record;
 mod search;
+mod selectors;
+mod source;
+mod store;
+mod summary;
+mod upstream;
 mod util;
 
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use parking_lot::RwLock;
+use reqwest::{
+    header::{HeaderMap, RETRY_AFTER},
+    StatusCode,
+};
 
 pub use assets::*;
 pub use cached::*;
+pub use canary::*;
 pub use error::*;
+pub use limit::*;
 pub use overbuff::*;
 pub use profile::*;
+pub use quarantine::*;
+pub use ranks::*;
+pub use record::*;
 pub use search::*;
+pub use selectors::*;
 pub use sombra_types::*;
+pub use source::*;
+pub use store::*;
+pub use upstream::*;
 
 use tracing::instrument;
 
+/// Upstream requests taking longer fail with a timeout.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[derive(Debug)]
 pub struct Client {
     client: reqwest::Client,
     assets: HashMap<Id, Asset>,
     heroes: Vec<Hero>,
+    sources: RwLock<Vec<RegisteredSource>>,
+    selectors: RwLock<Arc<Selectors>>,
+    quarantine: RwLock<Option<Quarantine>>,
+    recorder: RwLock<Option<Recorder>>,
+    upstream: Upstream,
+    limiter: Limiter,
 }
 
 impl Client {
     pub async fn new() -> Result<Self> {
+        Self::with_upstream(Upstream::default()).await
+    }
+
+    /// Like [`new`](Self::new), but scrapes the sites at `upstream`.
+    pub async fn with_upstream(upstream: Upstream) -> Result<Self> {
+        let mut s = Self::unfetched(upstream);
+        s.fetch_assets().await?;
+        s.fetch_heroes().await?;
+        Ok(s)
+    }
+
+    /// Client without assets and heroes, which are fetched by [`with_upstream`](Self::with_upstream).
+    fn unfetched(upstream: Upstream) -> Self {
         let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
             .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/109.0.0.0 Safari/537.36")
             .build()
             .expect("Could not build client");
-        let mut s = Self {
+        let s = Self {
             client,
             assets: HashMap::new(),
             heroes: Vec::new(),
+            sources: RwLock::default(),
+            selectors: RwLock::default(),
+            quarantine: RwLock::default(),
+            recorder: RwLock::default(),
+            upstream,
+            limiter: Limiter::new(UpstreamLimits::default()),
         };
-        s.fetch_assets().await?;
-        s.fetch_heroes().await?;
-        Ok(s)
+        s.register_source(CareerPageSource, CareerPageSource::PRIORITY);
+        s.register_source(OverbuffSource, OverbuffSource::PRIORITY);
+        s
     }
 
+    /// Sends a GET request, failing on any status other than 200.
     #[instrument(level = "debug", skip(self))]
-    async fn get(&self, url: &str) -> Result<String> {
+    pub async fn get(&self, url: &str) -> Result<String> {
+        let _permit = self.limiter.acquire().await;
         let response = self.client.get(url).send().await?;
-        Error::result_from_status(response.status(), None)?;
-        Ok(response.text().await?)
+        if self.recorder.read().is_none() {
+            check_status(response.status(), response.headers())?;
+            return Ok(response.text().await?);
+        }
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.text().await?;
+        if let Some(recorder) = &*self.recorder.read() {
+            recorder.record(url, status, &headers, &body);
+        }
+        check_status(status, &headers)?;
+        Ok(body)
+    }
+}
+
+/// Fails on any status other than 200, with the delay upstream asks for if it is rate limiting.
+fn check_status(status: StatusCode, headers: &HeaderMap) -> Result<()> {
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = headers
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok()?.parse().ok());
+        return Err(Error::RateLimited(retry_after));
     }
+    Error::result_from_status(status, None)
 }
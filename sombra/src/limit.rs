@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use tokio::{
+    sync::{Semaphore, SemaphorePermit},
+    time::Instant,
+};
+
+use crate::Client;
+
+/// Limits on the requests a [`Client`] sends upstream, shared by every caller. Requests over the
+/// limits wait instead of failing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UpstreamLimits {
+    /// Requests started per second. Unlimited if `None`.
+    pub requests_per_second: Option<u32>,
+    /// Requests in flight at once. Unlimited if `None`.
+    pub concurrency: Option<usize>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Limiter {
+    interval: Option<Duration>,
+    /// When the next request may start
+    next: Mutex<Instant>,
+    permits: Option<Semaphore>,
+}
+
+impl Limiter {
+    pub(crate) fn new(limits: UpstreamLimits) -> Self {
+        Self {
+            interval: limits
+                .requests_per_second
+                .filter(|&rate| rate > 0)
+                .map(|rate| Duration::from_secs(1) / rate),
+            next: Mutex::new(Instant::now()),
+            permits: limits.concurrency.map(Semaphore::new),
+        }
+    }
+
+    /// Waits until a request may be sent. It counts as in flight until the permit is dropped.
+    pub(crate) async fn acquire(&self) -> Option<SemaphorePermit<'_>> {
+        let permit = match &self.permits {
+            Some(permits) => Some(permits.acquire().await.expect("semaphore is never closed")),
+            None => None,
+        };
+        if let Some(interval) = self.interval {
+            let start = {
+                let mut next = self.next.lock();
+                let start = (*next).max(Instant::now());
+                *next = start + interval;
+                start
+            };
+            tokio::time::sleep_until(start).await;
+        }
+        permit
+    }
+}
+
+impl Client {
+    /// Applies `limits` to every following request.
+    #[must_use]
+    pub fn limit(mut self, limits: UpstreamLimits) -> Self {
+        self.limiter = Limiter::new(limits);
+        self
+    }
+}
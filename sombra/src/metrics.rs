@@ -0,0 +1,83 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use sombra_types::{CacheKindMetrics, CacheMetrics};
+
+use crate::CacheKind;
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    profile: Counters,
+    overbuff: Counters,
+    search: Counters,
+}
+
+#[derive(Debug, Default)]
+pub struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    fetches: AtomicU64,
+    coalesced: AtomicU64,
+    stale: AtomicU64,
+    revalidated: AtomicU64,
+}
+
+impl Metrics {
+    pub const fn get(&self, kind: CacheKind) -> &Counters {
+        match kind {
+            CacheKind::Profile => &self.profile,
+            CacheKind::Overbuff => &self.overbuff,
+            CacheKind::Search => &self.search,
+        }
+    }
+
+    pub fn snapshot(&self) -> CacheMetrics {
+        CacheMetrics {
+            profile: self.profile.snapshot(),
+            overbuff: self.overbuff.snapshot(),
+            search: self.search.snapshot(),
+        }
+    }
+}
+
+impl Counters {
+    pub fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn evict(&self) {
+        self.evictions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn fetch(&self) {
+        self.fetches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn coalesce(&self) {
+        self.coalesced.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn revalidate(&self) {
+        self.revalidated.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn serve_stale(&self) {
+        self.stale.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> CacheKindMetrics {
+        CacheKindMetrics {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            fetches: self.fetches.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+            stale: self.stale.load(Ordering::Relaxed),
+            revalidated: self.revalidated.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -1,56 +1,103 @@
-use sombra_types::{Battletag, Division, Group, Overbuff, Rank, Role};
+use futures::{future::BoxFuture, FutureExt};
+use sombra_types::{Battletag, Division, Overbuff, PageKind, Rank, SourceData};
 use tl::ParserOptions;
 use tracing::instrument;
 
 use crate::{
+    quarantine::Page,
     util::{find2, find_all, find_all2, find_attr2},
-    Client, Error,
+    Client, DataSource, Error, Selectors,
 };
 
+/// Ranks scraped from Overbuff. Only PC ranks are listed there.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OverbuffSource;
+
+impl OverbuffSource {
+    pub const NAME: &'static str = "overbuff";
+    pub const PRIORITY: i32 = 0;
+}
+
+impl DataSource for OverbuffSource {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        client: &'a Client,
+        btag: &'a Battletag,
+    ) -> BoxFuture<'a, crate::Result<SourceData>> {
+        async move {
+            let url = client.upstream().overbuff_url(btag);
+            let html = client.get(&url).await?;
+            let page = Page {
+                kind: PageKind::Overbuff,
+                url: &url,
+                btag: Some(btag),
+                parts: None,
+            };
+            let ranks = client.parse_page(&page, &html, |html| {
+                parse_overbuff(html, &client.selectors())
+            })?;
+            Ok(SourceData {
+                ranks,
+                heroes: Vec::new(),
+                last_updated: None,
+            })
+        }
+        .boxed()
+    }
+}
+
 impl Client {
     #[instrument(level = "debug", skip(self))]
     pub async fn overbuff(&self, btag: &Battletag) -> crate::Result<Overbuff> {
-        let mut ranks = Vec::new();
-        let url = "https://www.overbuff.com/players/";
-        let html = self.get(&format!("{url}{btag:#}/")).await?;
-        let dom = tl::parse(&html, ParserOptions::new())?;
-        let container = find_all(&dom, "div.flex.flex-row.justify-end.gap-x-4")
-            .nth(1)
-            .ok_or_else(Error::parse)?;
+        let data = OverbuffSource.fetch(self, btag).await?;
+        Ok(Overbuff { ranks: data.ranks })
+    }
 
-        for rank_container in find_all2(&dom, container, "div.flex") {
-            let role_len = find2(&dom, rank_container, "svg")
-                .ok_or_else(Error::parse)?
-                .inner_html(dom.parser())
-                .len();
-            let role = match role_len {
-                761 => Role::Tank,
-                1690 => Role::Damage,
-                1535 => Role::Support,
-                _ => return Err(Error::parse()),
-            };
-            let rank_str =
-                find_attr2(&dom, rank_container, "img", "alt").ok_or_else(Error::parse)?;
-            let split = rank_str.split_once(' ').ok_or_else(Error::parse)?;
-            let group = match split.0 {
-                "Bronze" => Group::Bronze,
-                "Silver" => Group::Silver,
-                "Gold" => Group::Gold,
-                "Platinum" => Group::Platinum,
-                "Diamond" => Group::Diamond,
-                "Master" => Group::Master,
-                "Grandmaster" => Group::Grandmaster,
-                _ => return Err(Error::parse()),
-            };
-            let division: Division = split.1.parse().map_err(|_| Error::parse())?;
-            ranks.push(Rank {
-                group,
-                division,
-                role,
-                console: false,
-            });
-        }
+    /// The unparsed Overbuff page of `btag`.
+    pub async fn overbuff_page(&self, btag: &Battletag) -> crate::Result<String> {
+        self.get(&self.upstream.overbuff_url(btag)).await
+    }
+}
+
+/// Parses the ranks on an Overbuff player page.
+pub fn parse_overbuff(html: &str, selectors: &Selectors) -> crate::Result<Vec<Rank>> {
+    let selectors = &selectors.overbuff;
+    let mut ranks = Vec::new();
+    let dom = tl::parse(html, ParserOptions::new())?;
+    let container = find_all(&dom, &selectors.rank_containers)
+        .nth(selectors.rank_container_index)
+        .ok_or_else(Error::parse)?;
 
-        Ok(Overbuff { ranks })
+    for rank_container in find_all2(&dom, container, &selectors.rank) {
+        let role_len = find2(&dom, rank_container, &selectors.role_icon)
+            .ok_or_else(Error::parse)?
+            .inner_html(dom.parser())
+            .len();
+        let role = *selectors
+            .roles
+            .get(&role_len.to_string())
+            .ok_or_else(Error::parse)?;
+        let rank_str = find_attr2(
+            &dom,
+            rank_container,
+            &selectors.rank_image,
+            &selectors.rank_image_attr,
+        )
+        .ok_or_else(Error::parse)?;
+        let split = rank_str.split_once(' ').ok_or_else(Error::parse)?;
+        let group = *selectors.tiers.get(split.0).ok_or_else(Error::parse)?;
+        let division: Division = split.1.parse().map_err(|_| Error::parse())?;
+        ranks.push(Rank {
+            group,
+            division,
+            role,
+            console: false,
+        });
     }
+
+    Ok(ranks)
 }
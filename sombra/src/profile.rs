@@ -1,140 +1,355 @@
-use crate::util::{
-    find, find2, find_all, find_all2, find_attr, find_attr2, find_inner_text, find_inner_text2,
-    url_file,
-};
-use crate::{Client, Error};
+use crate::util::{find, find_all, find_all2, find_attr, find_attr2, find_inner_text, url_file};
+use crate::{quarantine::Page, CareerSelectors, Client, DataSource, Error, Selectors};
 use chrono::{DateTime, TimeZone, Utc};
-use sombra_types::{Battletag, Division, Endorsement, Group, HeroStats, PlayerProfile, Rank, Role};
-use std::collections::HashMap;
-use tl::{ParserOptions, VDom};
+use futures::{future::BoxFuture, FutureExt};
+use sombra_types::{
+    Battletag, Division, Endorsement, HeroStats, PageKind, PlayerProfile, ProfileParts,
+    ProfileVisibility, Rank, SourceData, Stat,
+};
+use std::{borrow::Cow, collections::HashMap};
+use tl::{HTMLTag, Node, NodeHandle, Parser, ParserOptions, VDom};
 use tracing::instrument;
 use url::Url;
 
 impl Client {
     #[instrument(level = "debug", skip(self))]
-    pub async fn profile(&self, btag: &Battletag) -> crate::Result<PlayerProfile> {
-        let url = "https://overwatch.blizzard.com/en-us/career/";
-        let html = self.get(&format!("{url}{btag:#}/")).await?;
-        let dom = tl::parse(&html, ParserOptions::new())?;
-
-        let public = find(&dom, ".Profile-player--privateText").is_none();
-        let (quickplay_console, competitive_console, quickplay_pc, competitive_pc);
-        if public {
-            quickplay_console = hero_stats(&dom, true, true)?;
-            competitive_console = hero_stats(&dom, false, true)?;
-            quickplay_pc = hero_stats(&dom, true, false)?;
-            competitive_pc = hero_stats(&dom, false, false)?;
-        } else {
-            quickplay_console = HashMap::new();
-            competitive_console = HashMap::new();
-            quickplay_pc = HashMap::new();
-            competitive_pc = HashMap::new();
-        }
-
-        Ok(PlayerProfile {
-            battletag: btag.clone(),
-            title: find_inner_text(&dom, ".Profile-player--title"),
-            endorsement: endorsement(&dom)?,
-            portrait: portrait(&dom)?,
-            ranks: ranks(&dom)?,
-            private: !public,
-            last_updated: last_update(&dom)?,
-            quickplay_console,
-            competitive_console,
-            quickplay_pc,
-            competitive_pc,
+    /// Parts that are not selected are left empty.
+    pub async fn profile(
+        &self,
+        btag: &Battletag,
+        parts: ProfileParts,
+    ) -> crate::Result<PlayerProfile> {
+        let url = self.upstream.career_url(btag);
+        let html = self.get(&url).await?;
+        let page = Page {
+            kind: PageKind::Career,
+            url: &url,
+            btag: Some(btag),
+            parts: Some(parts),
+        };
+        self.parse_page(&page, &html, |html| {
+            parse_profile(btag, html, parts, &self.selectors())
         })
     }
+
+    /// The unparsed career page of `btag`.
+    pub async fn career_page(&self, btag: &Battletag) -> crate::Result<String> {
+        self.get(&self.upstream.career_url(btag)).await
+    }
 }
 
-#[instrument(level = "debug", skip_all)]
-fn hero_stats<'dom>(
-    dom: &'dom VDom<'dom>,
-    qp: bool,
-    console: bool,
-) -> crate::Result<HashMap<String, HeroStats>> {
-    let mut heroes = HashMap::new();
+/// Ranks and hero stats from the Blizzard career page.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CareerPageSource;
+
+impl CareerPageSource {
+    pub const NAME: &'static str = "careerPage";
+    pub const PRIORITY: i32 = 100;
+}
+
+impl DataSource for CareerPageSource {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        client: &'a Client,
+        btag: &'a Battletag,
+    ) -> BoxFuture<'a, crate::Result<SourceData>> {
+        async move { Ok(source_data(&client.profile(btag, ProfileParts::ALL).await?)) }.boxed()
+    }
+}
 
-    let view_selector = if console {
-        ".Profile-view.controller-view"
+/// The ranks and hero stats of `profile`.
+pub(crate) fn source_data(profile: &PlayerProfile) -> SourceData {
+    let mut names: Vec<_> = profile.tables().flat_map(HashMap::keys).collect();
+    names.sort();
+    names.dedup();
+    SourceData {
+        ranks: profile.ranks.clone(),
+        heroes: names
+            .into_iter()
+            .filter_map(|name| profile.hero_stats(name))
+            .collect(),
+        last_updated: Some(profile.last_updated),
+    }
+}
+
+/// Parses the career page of `btag`, leaving the parts that are not selected empty.
+pub fn parse_profile(
+    btag: &Battletag,
+    html: &str,
+    parts: ProfileParts,
+    selectors: &Selectors,
+) -> crate::Result<PlayerProfile> {
+    let selectors = &selectors.career;
+    let dom = tl::parse(html, ParserOptions::new())?;
+
+    let public = find(&dom, &selectors.private).is_none();
+    let [quickplay_pc, competitive_pc, quickplay_console, competitive_console] = if public {
+        hero_stats(&dom, parts, selectors)?.map(Some)
     } else {
-        ".Profile-view.mouseKeyboard-view"
+        Default::default()
     };
-    if let Some(view) = find(dom, view_selector) {
-        let container_selector = if qp {
-            ".stats.quickPlay-view"
+
+    Ok(PlayerProfile {
+        battletag: btag.clone(),
+        title: find_inner_text(&dom, &selectors.title),
+        endorsement: endorsement(&dom, selectors)?,
+        portrait: portrait(&dom, selectors)?,
+        ranks: if parts.ranks {
+            ranks(&dom, selectors)?
+        } else {
+            Vec::new()
+        },
+        visibility: if public {
+            ProfileVisibility::Public
         } else {
-            ".stats.competitive-view"
+            ProfileVisibility::Private
+        },
+        private: !public,
+        last_updated: last_update(&dom, selectors)?,
+        quickplay_console,
+        competitive_console,
+        quickplay_pc,
+        competitive_pc,
+    })
+}
+
+/// Stats of one platform and mode, keyed by the option id of the hero dropdown.
+#[derive(Default)]
+struct StatsTable<'dom> {
+    heroes: HashMap<Cow<'dom, str>, Cow<'dom, str>>,
+    stats: HashMap<&'dom str, HashMap<String, Stat>>,
+}
+
+/// Builds the stat tables of every platform and mode in a single walk over the page.
+struct StatsParser<'dom> {
+    parser: &'dom Parser<'dom>,
+    parts: ProfileParts,
+    selectors: &'dom CareerSelectors,
+    /// Indexed by [`table_index`]
+    tables: [Option<StatsTable<'dom>>; 4],
+}
+
+const fn table_index(console: bool, competitive: bool) -> usize {
+    console as usize * 2 + competitive as usize
+}
+
+/// Returns the quickplay and competitive stats of PC, then those of console.
+#[instrument(level = "debug", skip_all)]
+fn hero_stats<'dom>(
+    dom: &'dom VDom<'dom>,
+    parts: ProfileParts,
+    selectors: &'dom CareerSelectors,
+) -> crate::Result<[HashMap<String, HeroStats>; 4]> {
+    let mut parser = StatsParser {
+        parser: dom.parser(),
+        parts,
+        selectors,
+        tables: Default::default(),
+    };
+    if !(parts.pc || parts.console) {
+        return Ok(Default::default());
+    }
+    for child in dom.children() {
+        parser.walk(*child, None, None)?;
+    }
+    parser.finish()
+}
+
+impl<'dom> StatsParser<'dom> {
+    fn walk(
+        &mut self,
+        handle: NodeHandle,
+        mut console: Option<bool>,
+        mut table: Option<usize>,
+    ) -> crate::Result<()> {
+        let Some(tag) = handle.get(self.parser).and_then(Node::as_tag) else {
+            return Ok(());
         };
-        if let Some(container) = find2(dom, view, container_selector) {
-            if let Some(select) = find2(dom, container, ".Profile-dropdown") {
-                let mut stat_ids = HashMap::new();
-                for c in find_all2(dom, select, "option") {
-                    let id = c
-                        .attributes()
-                        .get("value")
-                        .flatten()
-                        .ok_or_else(Error::parse)?
-                        .as_utf8_str();
-                    stat_ids.insert(id, c.inner_text(dom.parser()));
+        let attributes = tag.attributes();
+        let selectors = self.selectors;
+        match (console, table) {
+            (None, _) if attributes.is_class_member(&selectors.view_class) => {
+                if attributes.is_class_member(&selectors.console_class) {
+                    console = Some(true);
+                } else if attributes.is_class_member(&selectors.pc_class) {
+                    console = Some(false);
+                }
+            }
+            (Some(console), None) if attributes.is_class_member(&selectors.stats_class) => {
+                let competitive = if attributes.is_class_member(&selectors.quickplay_class) {
+                    false
+                } else if attributes.is_class_member(&selectors.competitive_class) {
+                    true
+                } else {
+                    return self.walk_children(tag, Some(console), None);
+                };
+                let index = table_index(console, competitive);
+                // only the first container of a platform and mode is used
+                if !self.parts.stats(console, competitive) || self.tables[index].is_some() {
+                    return Ok(());
                 }
+                self.tables[index] = Some(StatsTable::default());
+                table = Some(index);
+            }
+            (_, Some(index)) if attributes.is_class_member(&selectors.dropdown_class) => {
+                return self.dropdown(tag, index);
+            }
+            (_, Some(index)) if attributes.is_class_member(&selectors.stats_container_class) => {
+                let prefix = selectors.hero_option_prefix.as_str();
+                let id = tag
+                    .attributes()
+                    .class_iter()
+                    .and_then(|mut c| c.find_map(|class| class.strip_prefix(prefix)));
+                if let Some(id) = id {
+                    return self.stats(tag, index, id);
+                }
+            }
+            _ => {}
+        }
+        self.walk_children(tag, console, table)
+    }
 
-                for (id, hero) in stat_ids {
-                    let stats_selector = format!(".stats-container.option-{id}");
-                    let stats = find2(dom, container, &stats_selector).ok_or_else(Error::parse)?;
-                    let mut hero_stats = HashMap::new();
-                    for stat in find_all2(dom, stats, ".stat-item") {
-                        let name = find_inner_text2(dom, stat, ".name").ok_or_else(Error::parse)?;
-                        let value =
-                            find_inner_text2(dom, stat, ".value").ok_or_else(Error::parse)?;
-                        hero_stats.insert(name, value.parse().map_err(|()| Error::parse())?);
-                    }
-                    heroes.insert(hero.to_string(), HeroStats { stats: hero_stats });
+    fn walk_children(
+        &mut self,
+        tag: &'dom HTMLTag<'dom>,
+        console: Option<bool>,
+        table: Option<usize>,
+    ) -> crate::Result<()> {
+        for child in tag.children().top().iter() {
+            self.walk(*child, console, table)?;
+        }
+        Ok(())
+    }
+
+    fn dropdown(&mut self, select: &'dom HTMLTag<'dom>, index: usize) -> crate::Result<()> {
+        let table = self.tables[index].get_or_insert_with(StatsTable::default);
+        for option in select.children().all(self.parser) {
+            let Some(option) = option.as_tag().filter(|t| t.name() == "option") else {
+                continue;
+            };
+            let id = option
+                .attributes()
+                .get("value")
+                .flatten()
+                .ok_or_else(Error::parse)?
+                .as_utf8_str();
+            table.heroes.insert(id, option.inner_text(self.parser));
+        }
+        Ok(())
+    }
+
+    fn stats(
+        &mut self,
+        container: &'dom HTMLTag<'dom>,
+        index: usize,
+        id: &'dom str,
+    ) -> crate::Result<()> {
+        let table = self.tables[index].get_or_insert_with(StatsTable::default);
+        if table.stats.contains_key(id) {
+            return Ok(());
+        }
+        let mut stats = HashMap::new();
+        let mut item: Option<(Option<String>, Option<String>)> = None;
+        for node in container.children().all(self.parser) {
+            let Some(tag) = node.as_tag() else {
+                continue;
+            };
+            let attributes = tag.attributes();
+            if attributes.is_class_member(&self.selectors.stat_item_class) {
+                if let Some(item) = item.replace((None, None)) {
+                    insert_stat(&mut stats, item)?;
+                }
+            } else if let Some((name, value)) = &mut item {
+                if name.is_none() && attributes.is_class_member(&self.selectors.stat_name_class) {
+                    *name = Some(tag.inner_text(self.parser).into());
+                } else if value.is_none()
+                    && attributes.is_class_member(&self.selectors.stat_value_class)
+                {
+                    *value = Some(tag.inner_text(self.parser).into());
                 }
             }
         }
+        if let Some(item) = item {
+            insert_stat(&mut stats, item)?;
+        }
+        table.stats.insert(id, stats);
+        Ok(())
+    }
+
+    fn finish(self) -> crate::Result<[HashMap<String, HeroStats>; 4]> {
+        let mut heroes: [HashMap<String, HeroStats>; 4] = Default::default();
+        for (table, heroes) in self.tables.into_iter().zip(&mut heroes) {
+            let Some(mut table) = table else {
+                continue;
+            };
+            for (id, hero) in table.heroes {
+                let stats = table.stats.remove(&*id).ok_or_else(Error::parse)?;
+                heroes.insert(hero.into_owned(), HeroStats { stats });
+            }
+        }
+        Ok(heroes)
     }
-    Ok(heroes)
+}
+
+fn insert_stat(
+    stats: &mut HashMap<String, Stat>,
+    (name, value): (Option<String>, Option<String>),
+) -> crate::Result<()> {
+    let name = name.ok_or_else(Error::parse)?;
+    let value = value.ok_or_else(Error::parse)?;
+    stats.insert(name, value.parse().map_err(|()| Error::parse())?);
+    Ok(())
 }
 
 #[instrument(level = "debug", skip_all)]
-fn ranks<'dom>(dom: &'dom VDom<'dom>) -> crate::Result<Vec<Rank>> {
+fn ranks<'dom>(
+    dom: &'dom VDom<'dom>,
+    selectors: &'dom CareerSelectors,
+) -> crate::Result<Vec<Rank>> {
     let mut ranks = Vec::new();
-    for rank_wrapper in find_all(dom, ".Profile-playerSummary--rankWrapper") {
-        let console = rank_wrapper.attributes().is_class_member("controller-view");
-        for role_wrapper in find_all2(dom, rank_wrapper, ".Profile-playerSummary--roleWrapper") {
-            let rank_url = find_attr2(dom, role_wrapper, ".Profile-playerSummary--rank", "src")
-                .ok_or_else(Error::parse)?;
+    for rank_wrapper in find_all(dom, &selectors.rank_wrapper) {
+        let console = rank_wrapper
+            .attributes()
+            .is_class_member(&selectors.console_class);
+        for role_wrapper in find_all2(dom, rank_wrapper, &selectors.role_wrapper) {
+            let rank_url =
+                find_attr2(dom, role_wrapper, &selectors.rank, "src").ok_or_else(Error::parse)?;
 
             let split = url_file(&rank_url)?
                 .split_once('-')
                 .ok_or_else(Error::parse)?;
-            let group = match split.0 {
-                "BronzeTier" => Group::Bronze,
-                "SilverTier" => Group::Silver,
-                "GoldTier" => Group::Gold,
-                "PlatinumTier" => Group::Platinum,
-                "DiamondTier" => Group::Diamond,
-                "MasterTier" => Group::Master,
-                "GrandmasterTier" => Group::Grandmaster,
-                _ => return Err(Error::parse()),
-            };
+            let group = *selectors.tiers.get(split.0).ok_or_else(Error::parse)?;
             #[allow(clippy::string_slice)]
             let division: Division = split.1[..1].parse().map_err(|_| Error::parse())?;
 
             let role_url = if console {
-                find_attr2(dom, role_wrapper, "[xlink:href]", "xlink:href")
-                    .ok_or_else(Error::parse)?
+                find_attr2(
+                    dom,
+                    role_wrapper,
+                    &selectors.console_role_icon,
+                    &selectors.console_role_icon_attr,
+                )
             } else {
-                find_attr2(dom, role_wrapper, "[src]", "src").ok_or_else(Error::parse)?
-            };
+                find_attr2(
+                    dom,
+                    role_wrapper,
+                    &selectors.pc_role_icon,
+                    &selectors.pc_role_icon_attr,
+                )
+            }
+            .ok_or_else(Error::parse)?;
 
-            let role = match url_file(&role_url)? {
-                url if url.starts_with("tank") => Role::Tank,
-                url if url.starts_with("offense") => Role::Damage,
-                url if url.starts_with("support") => Role::Support,
-                _ => return Err(Error::parse()),
-            };
+            let role_file = url_file(&role_url)?;
+            let role = selectors
+                .roles
+                .iter()
+                .find(|(prefix, _)| role_file.starts_with(prefix.as_str()))
+                .map(|(_, role)| *role)
+                .ok_or_else(Error::parse)?;
 
             ranks.push(Rank {
                 group,
@@ -148,25 +363,117 @@ fn ranks<'dom>(dom: &'dom VDom<'dom>) -> crate::Result<Vec<Rank>> {
 }
 
 #[instrument(level = "debug", skip_all)]
-fn endorsement<'dom>(dom: &'dom VDom<'dom>) -> crate::Result<Option<Endorsement>> {
-    let endorsement_url =
-        find_attr(dom, ".Profile-playerSummary--endorsement", "src").ok_or_else(Error::parse)?;
+fn endorsement<'dom>(
+    dom: &'dom VDom<'dom>,
+    selectors: &'dom CareerSelectors,
+) -> crate::Result<Option<Endorsement>> {
+    let endorsement_url = find_attr(dom, &selectors.endorsement, "src").ok_or_else(Error::parse)?;
 
     #[allow(clippy::string_slice)]
     Ok(url_file(&endorsement_url)?[..1].parse().ok())
 }
 
 #[instrument(level = "debug", skip_all)]
-fn portrait<'dom>(dom: &'dom VDom<'dom>) -> crate::Result<Url> {
-    find_attr(dom, ".Profile-player--portrait", "src")
+fn portrait<'dom>(dom: &'dom VDom<'dom>, selectors: &'dom CareerSelectors) -> crate::Result<Url> {
+    find_attr(dom, &selectors.portrait, "src")
         .ok_or_else(Error::parse)?
         .parse()
         .map_err(|_| Error::parse())
 }
 
 #[instrument(level = "debug", skip_all)]
-fn last_update<'dom>(dom: &'dom VDom<'dom>) -> crate::Result<DateTime<Utc>> {
-    let ts_str = find_attr(dom, ".Profile-masthead", "data-lastUpdate").ok_or_else(Error::parse)?;
+fn last_update<'dom>(
+    dom: &'dom VDom<'dom>,
+    selectors: &'dom CareerSelectors,
+) -> crate::Result<DateTime<Utc>> {
+    let ts_str = find_attr(dom, &selectors.masthead, &selectors.last_updated_attr)
+        .ok_or_else(Error::parse)?;
     let ts: i64 = ts_str.parse().map_err(|_| Error::parse())?;
     Utc.timestamp_opt(ts, 0).single().ok_or_else(Error::parse)
 }
+
+#[cfg(test)]
+mod tests {
+    use sombra_types::{Mode, Platform, ProfilePart};
+
+    use super::*;
+
+    const PUBLIC: &str = include_str!("../fixtures/career/public.html");
+    const PRIVATE: &str = include_str!("../fixtures/career/private.html");
+
+    fn parse(html: &str, parts: ProfileParts) -> PlayerProfile {
+        let btag = Battletag::new("Player", 1234);
+        parse_profile(&btag, html, parts, &Selectors::default()).unwrap()
+    }
+
+    #[test]
+    fn private_profiles_have_no_stats() {
+        let profile = parse(PRIVATE, ProfileParts::ALL);
+        assert!(profile.visibility.is_private() && profile.private);
+        assert_eq!(profile.stats(Platform::Pc, Mode::Competitive), None);
+        assert_eq!(profile.tables().count(), 0);
+
+        let json = serde_json::to_value(&profile).unwrap();
+        assert_eq!(json["visibility"], "private");
+        assert_eq!(json["private"], true);
+        assert!(json.get("competitivePc").is_none());
+        assert_eq!(
+            serde_json::from_value::<PlayerProfile>(json).unwrap(),
+            profile
+        );
+    }
+
+    #[test]
+    fn public_profiles_have_stats_even_if_empty() {
+        let profile = parse(PUBLIC, ProfileParts::ALL);
+        assert!(!profile.visibility.is_private() && !profile.private);
+        assert!(!profile
+            .stats(Platform::Pc, Mode::Competitive)
+            .unwrap()
+            .is_empty());
+
+        let profile = parse(PUBLIC, ProfileParts::REDUCED);
+        assert_eq!(
+            profile.stats(Platform::Pc, Mode::Competitive),
+            Some(&HashMap::new())
+        );
+        let json = serde_json::to_value(&profile).unwrap();
+        assert_eq!(json["private"], false);
+        assert_eq!(json["competitivePc"], serde_json::json!({}));
+    }
+
+    #[test]
+    fn each_part_parses_only_its_tables() {
+        let full = parse(PUBLIC, ProfileParts::ALL);
+        assert!(!full.ranks.is_empty());
+        for part in [
+            ProfilePart::Summary,
+            ProfilePart::Ranks,
+            ProfilePart::Pc,
+            ProfilePart::Console,
+            ProfilePart::Quickplay,
+            ProfilePart::Competitive,
+        ] {
+            let parts: ProfileParts = std::iter::once(part).collect();
+            let profile = parse(PUBLIC, parts);
+            assert_eq!(
+                (&profile.title, &profile.portrait, profile.last_updated),
+                (&full.title, &full.portrait, full.last_updated),
+                "{part:?}"
+            );
+            let ranks = if parts.ranks { &full.ranks[..] } else { &[] };
+            assert_eq!(profile.ranks, ranks, "{part:?}");
+            for platform in [Platform::Pc, Platform::Console] {
+                for mode in [Mode::Quickplay, Mode::Competitive] {
+                    let selected = ProfileParts::table(platform, mode);
+                    let stats = profile.stats(platform, mode).unwrap();
+                    if parts.contains(selected) {
+                        assert_eq!(Some(stats), full.stats(platform, mode), "{part:?}");
+                    } else {
+                        assert!(stats.is_empty(), "{part:?} parsed {platform:?} {mode:?}");
+                    }
+                }
+            }
+        }
+    }
+}
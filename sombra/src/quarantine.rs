@@ -0,0 +1,356 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde_derive::{Deserialize, Serialize};
+use sombra_types::{Battletag, Hero, PageKind, PlayerProfile, ProfilePart, ProfileParts, Rank};
+
+use crate::{heroes::parse_heroes, parse_overbuff, parse_profile, Client, Error, Selectors};
+
+pub const DEFAULT_MAX_PAGES: usize = 100;
+pub const DEFAULT_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Directory that upstream pages are written to when they fail to parse, so that the failure
+/// can be [replayed](replay) later. Every page is stored as `<id>.html` with its metadata in
+/// `<id>.json`. Once there are more than `max_pages` pages or they take up more than
+/// `max_bytes`, the oldest ones are removed.
+#[derive(Debug)]
+pub struct Quarantine {
+    dir: PathBuf,
+    max_pages: usize,
+    max_bytes: u64,
+    /// Held while writing and pruning
+    lock: Mutex<()>,
+}
+
+/// Metadata of a quarantined page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuarantinedPage {
+    pub kind: PageKind,
+    pub url: String,
+    pub battletag: Option<Battletag>,
+    /// Parts the career page was parsed with
+    #[serde(default)]
+    pub parts: Vec<ProfilePart>,
+    pub quarantined_at: DateTime<Utc>,
+    pub error: QuarantinedError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuarantinedError {
+    /// See [`Error::code`]
+    pub code: String,
+    pub message: String,
+}
+
+/// What a quarantined page parses to now.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Replayed {
+    Career(Box<PlayerProfile>),
+    Overbuff(Vec<Rank>),
+    Heroes(Vec<Hero>),
+}
+
+/// Page that is about to be parsed.
+pub(crate) struct Page<'a> {
+    pub kind: PageKind,
+    pub url: &'a str,
+    pub btag: Option<&'a Battletag>,
+    pub parts: Option<ProfileParts>,
+}
+
+impl Quarantine {
+    /// Uses [`DEFAULT_MAX_PAGES`] and [`DEFAULT_MAX_BYTES`] as limits.
+    pub fn new(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            max_pages: DEFAULT_MAX_PAGES,
+            max_bytes: DEFAULT_MAX_BYTES,
+            lock: Mutex::new(()),
+        })
+    }
+
+    #[must_use]
+    pub const fn limits(mut self, max_pages: usize, max_bytes: u64) -> Self {
+        self.max_pages = max_pages;
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Metadata files of the quarantined pages, oldest first.
+    pub fn pages(&self) -> std::io::Result<Vec<PathBuf>> {
+        let mut pages = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                pages.push(path);
+            }
+        }
+        // ids start with the time of quarantine
+        pages.sort();
+        Ok(pages)
+    }
+
+    pub(crate) fn write(&self, page: &Page<'_>, html: &str, error: &Error) {
+        if html.len() as u64 > self.max_bytes {
+            tracing::warn!(
+                url = page.url,
+                size = html.len(),
+                "Page too large to quarantine"
+            );
+            return;
+        }
+        let quarantined_at = Utc::now();
+        let metadata = QuarantinedPage {
+            kind: page.kind,
+            url: page.url.to_owned(),
+            battletag: page.btag.cloned(),
+            parts: page.parts.map(|p| p.parts()).unwrap_or_default(),
+            quarantined_at,
+            error: QuarantinedError {
+                code: error.code().to_owned(),
+                message: error.to_string(),
+            },
+        };
+        let kind = match page.kind {
+            PageKind::Career => "career",
+            PageKind::Overbuff => "overbuff",
+            PageKind::Heroes => "heroes",
+        };
+        let id = format!("{}-{kind}", quarantined_at.format("%Y%m%dT%H%M%S%9fZ"));
+        let path = self.dir.join(id);
+
+        let _lock = self.lock.lock();
+        let json = serde_json::to_vec_pretty(&metadata).expect("metadata is serializable");
+        let written = fs::write(path.with_extension("html"), html)
+            .and_then(|()| fs::write(path.with_extension("json"), json));
+        match written {
+            Ok(()) => tracing::info!(?path, url = page.url, "Quarantined page"),
+            Err(error) => tracing::warn!(?path, %error, "Could not quarantine page"),
+        }
+        if let Err(error) = self.prune() {
+            tracing::warn!(dir = ?self.dir, %error, "Could not prune quarantine");
+        }
+    }
+
+    /// Removes the oldest pages until both limits are met.
+    fn prune(&self) -> std::io::Result<()> {
+        let pages = self.pages()?;
+        let sizes: Vec<u64> = pages
+            .iter()
+            .map(|path| {
+                [path.clone(), path.with_extension("html")]
+                    .iter()
+                    .filter_map(|p| fs::metadata(p).ok())
+                    .map(|m| m.len())
+                    .sum()
+            })
+            .collect();
+        let mut count = pages.len();
+        let mut bytes: u64 = sizes.iter().sum();
+        for (path, size) in pages.iter().zip(sizes) {
+            if count <= self.max_pages && bytes <= self.max_bytes {
+                break;
+            }
+            let _ = fs::remove_file(path.with_extension("html"));
+            fs::remove_file(path)?;
+            count -= 1;
+            bytes -= size;
+        }
+        Ok(())
+    }
+}
+
+impl QuarantinedPage {
+    /// Reads the metadata of the page at `path`, which may be either of its two files.
+    pub fn load(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let json = fs::read(path.as_ref().with_extension("json"))?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+/// Parses the quarantined page at `path` again, which may be either of its two files, with the
+/// same parser and parts that failed on it.
+pub fn replay(path: impl AsRef<Path>, selectors: &Selectors) -> crate::Result<Replayed> {
+    let path = path.as_ref();
+    let metadata = QuarantinedPage::load(path)?;
+    let html = fs::read_to_string(path.with_extension("html"))?;
+    Ok(match metadata.kind {
+        PageKind::Career => {
+            let btag = metadata
+                .battletag
+                .unwrap_or_else(|| Battletag::new("Quarantined", 1));
+            let parts = if metadata.parts.is_empty() {
+                ProfileParts::ALL
+            } else {
+                metadata.parts.into_iter().collect()
+            };
+            Replayed::Career(Box::new(parse_profile(&btag, &html, parts, selectors)?))
+        }
+        PageKind::Overbuff => Replayed::Overbuff(parse_overbuff(&html, selectors)?),
+        PageKind::Heroes => Replayed::Heroes(parse_heroes(&html, selectors)?),
+    })
+}
+
+impl Client {
+    /// Writes pages that fail to parse to `quarantine`, or stops doing so if it is `None`.
+    pub fn set_quarantine(&self, quarantine: Option<Quarantine>) {
+        *self.quarantine.write() = quarantine;
+    }
+
+    /// Parses `html` with `parse`, quarantining the page if that fails.
+    pub(crate) fn parse_page<T>(
+        &self,
+        page: &Page<'_>,
+        html: &str,
+        parse: impl FnOnce(&str) -> crate::Result<T>,
+    ) -> crate::Result<T> {
+        let result = parse(html);
+        if let Err(error) = &result {
+            if let Some(quarantine) = &*self.quarantine.read() {
+                quarantine.write(page, html, error);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAREER: &str = include_str!("../fixtures/career/public.html");
+
+    fn quarantine(max_pages: usize, max_bytes: u64) -> (tempfile::TempDir, Quarantine) {
+        let dir = tempfile::tempdir().unwrap();
+        let quarantine = Quarantine::new(dir.path())
+            .unwrap()
+            .limits(max_pages, max_bytes);
+        (dir, quarantine)
+    }
+
+    fn write(quarantine: &Quarantine, btag: &Battletag, parts: Option<ProfileParts>, html: &str) {
+        let page = Page {
+            kind: PageKind::Career,
+            url: "https://overwatch.blizzard.com/en-us/career/Player-1234/",
+            btag: Some(btag),
+            parts,
+        };
+        quarantine.write(&page, html, &Error::Parse);
+    }
+
+    fn battletags(quarantine: &Quarantine) -> Vec<Battletag> {
+        quarantine
+            .pages()
+            .unwrap()
+            .iter()
+            .map(|path| QuarantinedPage::load(path).unwrap().battletag.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn pages_are_written_with_their_metadata() {
+        let (_dir, quarantine) = quarantine(10, DEFAULT_MAX_BYTES);
+        let btag = Battletag::new("Player", 1234);
+        write(
+            &quarantine,
+            &btag,
+            Some(ProfileParts::REDUCED),
+            "<html></html>",
+        );
+        let pages = quarantine.pages().unwrap();
+        assert_eq!(pages.len(), 1);
+        let metadata = QuarantinedPage::load(&pages[0]).unwrap();
+        assert_eq!(metadata.kind, PageKind::Career);
+        assert_eq!(metadata.battletag, Some(btag));
+        assert_eq!(metadata.parts, ProfileParts::REDUCED.parts());
+        assert_eq!(metadata.error.code, Error::Parse.code());
+        let html = fs::read_to_string(pages[0].with_extension("html")).unwrap();
+        assert_eq!(html, "<html></html>");
+    }
+
+    #[test]
+    fn the_oldest_pages_are_pruned_beyond_the_count() {
+        let (_dir, quarantine) = quarantine(2, DEFAULT_MAX_BYTES);
+        for discriminator in 1..=3 {
+            write(
+                &quarantine,
+                &Battletag::new("Player", discriminator),
+                None,
+                "<html></html>",
+            );
+        }
+        let expected = [Battletag::new("Player", 2), Battletag::new("Player", 3)];
+        assert_eq!(battletags(&quarantine), expected);
+        let files = fs::read_dir(quarantine.dir()).unwrap().count();
+        assert_eq!(files, 4);
+    }
+
+    #[test]
+    fn the_oldest_pages_are_pruned_beyond_the_size() {
+        let html = "x".repeat(1000);
+        let (_dir, quarantine) = quarantine(10, 2500);
+        for discriminator in 1..=3 {
+            write(
+                &quarantine,
+                &Battletag::new("Player", discriminator),
+                None,
+                &html,
+            );
+        }
+        assert_eq!(battletags(&quarantine), [Battletag::new("Player", 3)]);
+    }
+
+    #[test]
+    fn pages_larger_than_the_size_are_not_written() {
+        let (_dir, quarantine) = quarantine(10, 100);
+        write(
+            &quarantine,
+            &Battletag::new("Player", 1),
+            None,
+            &"x".repeat(101),
+        );
+        assert!(quarantine.pages().unwrap().is_empty());
+    }
+
+    #[test]
+    fn replay_parses_with_the_quarantined_parts() {
+        let (_dir, quarantine) = quarantine(10, DEFAULT_MAX_BYTES);
+        let btag = Battletag::new("Player", 1234);
+        write(&quarantine, &btag, Some(ProfileParts::REDUCED), CAREER);
+        let path = &quarantine.pages().unwrap()[0];
+        let selectors = Selectors::default();
+        let Replayed::Career(profile) = replay(path, &selectors).unwrap() else {
+            panic!("replayed a career page as another kind");
+        };
+        let expected = parse_profile(&btag, CAREER, ProfileParts::REDUCED, &selectors).unwrap();
+        assert_eq!(*profile, expected);
+        // either file of the page can be replayed
+        let html = path.with_extension("html");
+        assert_eq!(replay(html, &selectors).unwrap(), Replayed::Career(profile));
+    }
+
+    #[test]
+    fn replay_fails_if_the_page_still_does_not_parse() {
+        let (_dir, quarantine) = quarantine(10, DEFAULT_MAX_BYTES);
+        write(
+            &quarantine,
+            &Battletag::new("Player", 1),
+            None,
+            "<html></html>",
+        );
+        let path = &quarantine.pages().unwrap()[0];
+        assert!(replay(path, &Selectors::default()).is_err());
+    }
+}
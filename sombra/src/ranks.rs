@@ -0,0 +1,335 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use sombra_types::{
+    Battletag, PlayerProfile, ProfileParts, Rank, RankConflict, ReconciledRanks, SourceData,
+    SourcedRank,
+};
+
+use crate::{CacheStore, Cached, CachedClient, CareerPageSource, RegisteredSource};
+
+/// Ranks observed by the registered sources.
+pub(crate) struct Observations {
+    /// The career page, parsed with the requested parts
+    pub profile: crate::Result<Cached<Arc<PlayerProfile>>>,
+    pub ranks: Vec<SourcedRank>,
+    /// Number of sources that could be fetched
+    pub responded: usize,
+    /// The sources that were asked, whose priorities decide between simultaneous observations
+    pub sources: Vec<RegisteredSource>,
+    /// Freshness of the sources that could be fetched
+    pub cached: Cached<()>,
+}
+
+impl<S: CacheStore + 'static> CachedClient<S> {
+    /// Ranks from every registered source, as fresh as the oldest of them. Fails only if none of
+    /// them could be fetched.
+    pub async fn ranks(&self, btag: &Battletag) -> crate::Result<Cached<ReconciledRanks>> {
+        let observations = self.observe_ranks(btag, ProfileParts::REDUCED).await;
+        if observations.responded == 0 {
+            // The career page is always registered, so its error is the one to report
+            observations.profile?;
+        }
+        let reconciled = reconcile_ranks(observations.ranks, &observations.sources);
+        for conflict in &reconciled.conflicts {
+            tracing::info!(?btag, ?conflict, "Sources disagree on rank");
+        }
+        Ok(observations.cached.map(|()| reconciled))
+    }
+
+    /// Fetches every registered source concurrently. Sources that fail are logged and skipped.
+    pub(crate) async fn observe_ranks(
+        &self,
+        btag: &Battletag,
+        parts: ProfileParts,
+    ) -> Observations {
+        let sources = self.sources();
+        let (profile, third_party) = futures::join!(
+            self.profile_parts(btag, parts),
+            self.third_party(btag, &sources)
+        );
+        observations(btag, sources, profile, third_party)
+    }
+
+    /// Fetches the career page, and the other registered sources only if it is private, has no
+    /// ranks or could not be fetched.
+    pub(crate) async fn observe_ranks_fallback(
+        &self,
+        btag: &Battletag,
+        parts: ProfileParts,
+    ) -> Observations {
+        let sources = self.sources();
+        let profile = self.profile_parts(btag, parts).await;
+        let fallback = profile.as_ref().map_or(true, |p| {
+            p.value.visibility.is_private() || p.value.ranks.is_empty()
+        });
+        let third_party = if fallback {
+            self.third_party(btag, &sources).await
+        } else {
+            Vec::new()
+        };
+        observations(btag, sources, profile, third_party)
+    }
+
+    /// Fetches the sources other than the career page concurrently, in the order of `sources`.
+    async fn third_party(
+        &self,
+        btag: &Battletag,
+        sources: &[RegisteredSource],
+    ) -> Vec<crate::Result<Cached<Arc<SourceData>>>> {
+        let third_party = sources
+            .iter()
+            .filter(|s| s.source.name() != CareerPageSource::NAME)
+            .map(|s| self.source(s.source.name(), btag));
+        futures::future::join_all(third_party).await
+    }
+}
+
+/// The ranks of `sources` from the career page and those of the other sources that were
+/// fetched. Failed sources are logged and skipped.
+///
+/// Ranks are observed when their source last saw them change. Values of sources that do not
+/// report that and were fetched for this call are observed at the same instant, so that the
+/// priority of their sources decides between them rather than the order they arrived in.
+fn observations(
+    btag: &Battletag,
+    sources: Vec<RegisteredSource>,
+    profile: crate::Result<Cached<Arc<PlayerProfile>>>,
+    third_party: Vec<crate::Result<Cached<Arc<SourceData>>>>,
+) -> Observations {
+    let now = Utc::now();
+    let mut third_party = third_party.into_iter();
+    let mut observations = Observations {
+        ranks: Vec::new(),
+        responded: 0,
+        profile,
+        sources: Vec::new(),
+        cached: Cached::unit(),
+    };
+    for registered in &sources {
+        let name = registered.source.name();
+        let ranks = if name == CareerPageSource::NAME {
+            match &observations.profile {
+                Ok(profile) => {
+                    observations.cached = observations.cached.merge(profile);
+                    let observed = Some(profile.value.last_updated);
+                    observe(now, profile, observed, &profile.value.ranks, name)
+                }
+                Err(error) => {
+                    tracing::warn!(?btag, source = name, %error, "Data source failed");
+                    continue;
+                }
+            }
+        } else {
+            match third_party.next() {
+                Some(Ok(data)) => {
+                    observations.cached = observations.cached.merge(&data);
+                    observe(now, &data, data.value.last_updated, &data.value.ranks, name)
+                }
+                Some(Err(error)) => {
+                    tracing::warn!(?btag, source = name, %error, "Data source failed");
+                    continue;
+                }
+                None => continue,
+            }
+        };
+        observations.ranks.extend(ranks);
+        observations.responded += 1;
+    }
+    observations.sources = sources;
+    observations
+}
+
+/// Tags `ranks` with `source` and when they were `last_updated`, or else when the cached value
+/// was fetched, relative to `now`.
+fn observe<T>(
+    now: DateTime<Utc>,
+    cached: &Cached<T>,
+    last_updated: Option<DateTime<Utc>>,
+    ranks: &[Rank],
+    source: &str,
+) -> Vec<SourcedRank> {
+    let observed = last_updated.unwrap_or_else(|| {
+        now - chrono::Duration::from_std(cached.age).unwrap_or_else(|_| chrono::Duration::zero())
+    });
+    ranks
+        .iter()
+        .map(|rank| SourcedRank {
+            rank: rank.clone(),
+            source: source.to_owned(),
+            observed,
+        })
+        .collect()
+}
+
+/// Picks the most recent observation for every role and platform. Between observations made
+/// at the same time, the one whose source has the highest priority in `sources` wins; sources
+/// that are not in `sources` come last. Observations that disagree with the chosen one are
+/// reported as conflicts.
+#[must_use]
+pub fn reconcile_ranks(
+    mut observations: Vec<SourcedRank>,
+    sources: &[RegisteredSource],
+) -> ReconciledRanks {
+    let priority = |observation: &SourcedRank| {
+        sources
+            .iter()
+            .find(|s| s.source.name() == observation.source)
+            .map_or(i32::MIN, |s| s.priority)
+    };
+    observations.sort_by(|a, b| {
+        (a.rank.console, a.rank.role)
+            .cmp(&(b.rank.console, b.rank.role))
+            .then(b.observed.cmp(&a.observed))
+            .then(priority(b).cmp(&priority(a)))
+    });
+    let mut reconciled = ReconciledRanks::default();
+    let mut rest = observations.as_slice();
+    while let Some(chosen) = rest.first() {
+        let len = rest
+            .iter()
+            .take_while(|o| {
+                (o.rank.console, o.rank.role) == (chosen.rank.console, chosen.rank.role)
+            })
+            .count();
+        let (group, tail) = rest.split_at(len);
+        rest = tail;
+        let disagree = group
+            .iter()
+            .any(|o| (o.rank.group, o.rank.division) != (chosen.rank.group, chosen.rank.division));
+        if disagree {
+            reconciled.conflicts.push(RankConflict {
+                role: chosen.rank.role,
+                console: chosen.rank.console,
+                ranks: group.to_vec(),
+            });
+        }
+        reconciled.ranks.push(chosen.clone());
+    }
+    reconciled
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use sombra_types::{Division, Group, Role};
+
+    use super::*;
+    use crate::{DataSource, OverbuffSource};
+
+    fn sources() -> Vec<RegisteredSource> {
+        let source = |source: Arc<dyn DataSource>, priority| RegisteredSource { source, priority };
+        vec![
+            source(Arc::new(CareerPageSource), CareerPageSource::PRIORITY),
+            source(Arc::new(OverbuffSource), OverbuffSource::PRIORITY),
+        ]
+    }
+
+    fn rank(group: Group, role: Role) -> Rank {
+        Rank {
+            group,
+            division: Division::new(3).unwrap(),
+            role,
+            console: false,
+        }
+    }
+
+    fn sourced(rank: Rank, source: &str, observed: DateTime<Utc>) -> SourcedRank {
+        SourcedRank {
+            rank,
+            source: source.to_owned(),
+            observed,
+        }
+    }
+
+    #[test]
+    fn priority_decides_between_simultaneous_observations() {
+        let now = Utc::now();
+        let career = sourced(rank(Group::Gold, Role::Tank), CareerPageSource::NAME, now);
+        let overbuff = sourced(rank(Group::Silver, Role::Tank), OverbuffSource::NAME, now);
+        for observations in [
+            vec![career.clone(), overbuff.clone()],
+            vec![overbuff.clone(), career.clone()],
+        ] {
+            let reconciled = reconcile_ranks(observations, &sources());
+            assert_eq!(reconciled.ranks, std::slice::from_ref(&career));
+            assert_eq!(reconciled.conflicts.len(), 1);
+            assert_eq!(
+                reconciled.conflicts[0].ranks,
+                [career.clone(), overbuff.clone()]
+            );
+        }
+    }
+
+    #[test]
+    fn fresh_observations_win_over_stale_ones() {
+        let now = Utc::now();
+        let stale = now - chrono::Duration::hours(1);
+        let career = sourced(rank(Group::Gold, Role::Tank), CareerPageSource::NAME, stale);
+        let overbuff = sourced(rank(Group::Silver, Role::Tank), OverbuffSource::NAME, now);
+        let reconciled = reconcile_ranks(vec![career.clone(), overbuff.clone()], &sources());
+        assert_eq!(reconciled.ranks, std::slice::from_ref(&overbuff));
+        assert_eq!(reconciled.conflicts[0].ranks, [overbuff, career]);
+    }
+
+    #[test]
+    fn agreeing_sources_are_no_conflict() {
+        let now = Utc::now();
+        let observations = vec![
+            sourced(rank(Group::Gold, Role::Tank), CareerPageSource::NAME, now),
+            sourced(rank(Group::Gold, Role::Tank), OverbuffSource::NAME, now),
+            sourced(
+                rank(Group::Master, Role::Support),
+                OverbuffSource::NAME,
+                now,
+            ),
+        ];
+        let reconciled = reconcile_ranks(observations.clone(), &sources());
+        assert_eq!(
+            reconciled.ranks,
+            [observations[0].clone(), observations[2].clone()]
+        );
+        assert!(reconciled.conflicts.is_empty());
+    }
+
+    #[test]
+    fn unknown_sources_come_last() {
+        let now = Utc::now();
+        let unknown = sourced(rank(Group::Bronze, Role::Damage), "tracker", now);
+        let overbuff = sourced(rank(Group::Silver, Role::Damage), OverbuffSource::NAME, now);
+        let reconciled = reconcile_ranks(vec![unknown, overbuff.clone()], &sources());
+        assert_eq!(reconciled.ranks, [overbuff]);
+    }
+
+    #[test]
+    fn values_fetched_for_the_same_call_are_observed_together() {
+        let now = Utc::now();
+        let fetched = Cached::from(());
+        let cached = Cached {
+            age: Duration::from_secs(60),
+            ..Cached::from(())
+        };
+        let ranks = [rank(Group::Gold, Role::Tank)];
+        let career = observe(now, &fetched, None, &ranks, CareerPageSource::NAME);
+        let overbuff = observe(now, &fetched, None, &ranks, OverbuffSource::NAME);
+        assert_eq!(career[0].observed, overbuff[0].observed);
+        let older = observe(now, &cached, None, &ranks, OverbuffSource::NAME);
+        assert_eq!(older[0].observed, now - chrono::Duration::seconds(60));
+    }
+
+    #[test]
+    fn values_are_observed_when_they_were_last_updated() {
+        let now = Utc::now();
+        let updated = now - chrono::Duration::days(2);
+        let cached = Cached {
+            age: Duration::from_secs(60),
+            ..Cached::from(())
+        };
+        let ranks = [rank(Group::Gold, Role::Tank)];
+        for cached in [Cached::from(()), cached] {
+            let observed = observe(now, &cached, Some(updated), &ranks, OverbuffSource::NAME);
+            assert_eq!(observed[0].observed, updated);
+        }
+    }
+}
@@ -0,0 +1,391 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use percent_encoding::percent_decode_str;
+use reqwest::{header::HeaderMap, StatusCode};
+use serde_derive::{Deserialize, Serialize};
+use url::Url;
+
+use crate::Client;
+
+/// Appended to the name of a recorded body for the file holding its [`Recording`].
+pub const RECORDING_SUFFIX: &str = ".meta.json";
+
+/// Headers that describe the transfer rather than the body, which is stored decoded.
+const TRANSFER_HEADERS: [&str; 4] = [
+    "connection",
+    "content-encoding",
+    "content-length",
+    "transfer-encoding",
+];
+
+/// Path segments that are followed by a battletag or player name.
+const PLAYER_SEGMENTS: [&str; 3] = ["career", "players", "account-by-name"];
+
+/// Writes every upstream response to a directory in the layout that `sombra-mock` serves, so
+/// that a fixture corpus can be captured by simply using the API. The body of a response to
+/// `https://<host>/<path>` is written to [`recording_path`], its status and headers next to it.
+///
+/// With [`redact`](Self::redact), player names in URLs and bodies are replaced with aliases.
+/// This is best effort: names are only replaced where they appear as part of a battletag, as a
+/// path segment or as the whole text of an element.
+#[derive(Debug)]
+pub struct Recorder {
+    dir: PathBuf,
+    redact: bool,
+    /// Names seen in URLs and search results, replaced in every following response
+    names: Mutex<HashSet<String>>,
+}
+
+/// Status and headers of a recorded response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Recording {
+    pub url: String,
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl Recorder {
+    pub fn new(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            redact: false,
+            names: Mutex::default(),
+        })
+    }
+
+    #[must_use]
+    pub const fn redact(mut self, redact: bool) -> Self {
+        self.redact = redact;
+        self
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub(crate) fn record(&self, url: &str, status: StatusCode, headers: &HeaderMap, body: &str) {
+        let Some(path) = Url::parse(url).ok().and_then(|url| {
+            let path = percent_decode_str(url.path()).decode_utf8_lossy();
+            Some(format!("/{}{path}", url.host_str()?))
+        }) else {
+            tracing::warn!(url, "Could not record response");
+            return;
+        };
+
+        let mut recording = Recording {
+            url: url.to_owned(),
+            status: status.as_u16(),
+            headers: BTreeMap::new(),
+            recorded_at: Utc::now(),
+        };
+        for (name, value) in headers {
+            let name = name.as_str();
+            if TRANSFER_HEADERS.contains(&name) || (self.redact && name == "set-cookie") {
+                continue;
+            }
+            let Ok(value) = value.to_str() else {
+                continue;
+            };
+            recording
+                .headers
+                .entry(name.to_owned())
+                .and_modify(|v| *v = format!("{v}, {value}"))
+                .or_insert_with(|| value.to_owned());
+        }
+
+        let (path, body) = if self.redact {
+            let mut names = self.names.lock();
+            names.extend(player_names(&path, body));
+            recording.url = redact_names(&names, &recording.url);
+            (redact_names(&names, &path), redact_names(&names, body))
+        } else {
+            (path, body.to_owned())
+        };
+        let Some(file) = recording_path(&self.dir, &path) else {
+            tracing::warn!(
+                url,
+                "Could not record response outside of the recording directory"
+            );
+            return;
+        };
+        let json = serde_json::to_vec_pretty(&recording).expect("recording is serializable");
+        let written = file
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&file, body))
+            .and_then(|()| fs::write(metadata_path(&file), json));
+        match written {
+            Ok(()) => tracing::debug!(?file, "Recorded response"),
+            Err(error) => tracing::warn!(?file, %error, "Could not record response"),
+        }
+    }
+}
+
+impl Recording {
+    /// Reads the status and headers recorded for the body in `file`, if there are any.
+    pub fn load(file: impl AsRef<Path>) -> crate::Result<Option<Self>> {
+        let path = metadata_path(file.as_ref());
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_slice(&fs::read(path)?)?))
+    }
+}
+
+/// The file that the body of a response to `path`, as `/<host>/<path>`, is recorded in, with
+/// `index.html` appended to paths ending in a slash. `None` if `path` would leave `dir`.
+#[must_use]
+pub fn recording_path(dir: &Path, path: &str) -> Option<PathBuf> {
+    let relative = Path::new(path.trim_start_matches('/'));
+    if !relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+    let mut file = dir.join(relative);
+    if path.ends_with('/') {
+        file.push("index.html");
+    }
+    Some(file)
+}
+
+fn metadata_path(file: &Path) -> PathBuf {
+    let mut name = file.file_name().unwrap_or_default().to_owned();
+    name.push(RECORDING_SUFFIX);
+    file.with_file_name(name)
+}
+
+/// Names of the players in the URL `path` and, for search results, the body.
+fn player_names(path: &str, body: &str) -> Vec<String> {
+    let segments: Vec<&str> = path.split('/').collect();
+    let mut names: Vec<String> = segments
+        .windows(2)
+        .filter(|w| PLAYER_SEGMENTS.contains(&w[0]))
+        .map(|w| match w[1].rsplit_once('-') {
+            Some((name, number)) if number.bytes().all(|b| b.is_ascii_digit()) => name,
+            _ => w[1],
+        })
+        .filter(|name| !name.is_empty())
+        .map(str::to_owned)
+        .collect();
+    if let Ok(results) = serde_json::from_str::<Vec<serde_json::Value>>(body) {
+        names.extend(
+            results
+                .iter()
+                .filter_map(|r| r.get("battleTag")?.as_str()?.rsplit_once('#'))
+                .map(|(name, _)| name.to_owned()),
+        );
+    }
+    names
+}
+
+fn redact_names(names: &HashSet<String>, text: &str) -> String {
+    let mut text = text.to_owned();
+    for name in names {
+        text = redact_name(&text, name);
+    }
+    text
+}
+
+/// Replaces `name` wherever it is followed by a battletag number, ends a path segment or is the
+/// whole text of an element.
+fn redact_name(text: &str, name: &str) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut last = 0;
+    for (i, _) in text.match_indices(name) {
+        let before = text[..i].chars().next_back();
+        let after = &text[i + name.len()..];
+        if i < last || before.is_some_and(char::is_alphanumeric) {
+            continue;
+        }
+        let number = after
+            .strip_prefix(['#', '-'])
+            .is_some_and(|n| n.starts_with(|c: char| c.is_ascii_digit()));
+        let segment = before == Some('/') && (after.is_empty() || after.starts_with(['/', '?']));
+        let element = before == Some('>') && after.starts_with('<');
+        if number || segment || element {
+            redacted.push_str(&text[last..i]);
+            redacted.push_str(&alias(name));
+            last = i + name.len();
+        }
+    }
+    redacted.push_str(&text[last..]);
+    redacted
+}
+
+/// Stable across recordings, so that pages recorded at different times stay consistent.
+fn alias(name: &str) -> String {
+    // FNV-1a
+    let hash = name
+        .to_lowercase()
+        .bytes()
+        .fold(0x811c_9dc5_u32, |hash, b| {
+            (hash ^ u32::from(b)).wrapping_mul(0x0100_0193)
+        });
+    format!("Redacted{hash:08x}")
+}
+
+impl Client {
+    /// Records every following response to `recorder`, or stops doing so if it is `None`. Pages
+    /// fetched by [`Client::new`] are only recorded once they are fetched again.
+    pub fn set_recorder(&self, recorder: Option<Recorder>) {
+        *self.recorder.write() = recorder;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{HeaderValue, CONTENT_ENCODING, CONTENT_TYPE, SET_COOKIE};
+
+    use super::*;
+
+    const CAREER_URL: &str = "https://overwatch.blizzard.com/en-us/career/Player-1234/";
+
+    fn headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("br"));
+        headers.insert(SET_COOKIE, HeaderValue::from_static("session=secret"));
+        headers
+    }
+
+    fn read(dir: &Path, path: &str) -> (String, Recording) {
+        let file = recording_path(dir, path).unwrap();
+        let body = fs::read_to_string(&file).unwrap();
+        (body, Recording::load(&file).unwrap().unwrap())
+    }
+
+    #[test]
+    fn names_are_replaced_in_battletags_segments_and_elements() {
+        let alias = alias("Player");
+        for (text, redacted) in [
+            ("Player#1234", format!("{alias}#1234")),
+            ("/career/Player-1234/", format!("/career/{alias}-1234/")),
+            (
+                "/account-by-name/Player",
+                format!("/account-by-name/{alias}"),
+            ),
+            ("/players/Player?x=1", format!("/players/{alias}?x=1")),
+            ("<h1>Player</h1>", format!("<h1>{alias}</h1>")),
+        ] {
+            assert_eq!(redact_name(text, "Player"), redacted);
+        }
+    }
+
+    #[test]
+    fn names_within_other_text_are_kept() {
+        for text in [
+            "Players#1234",
+            "NewPlayer#1234",
+            "<p>Player of the game</p>",
+            "/career/Player.png",
+            "Player#",
+        ] {
+            assert_eq!(redact_name(text, "Player"), text);
+        }
+    }
+
+    #[test]
+    fn aliases_are_stable_and_ignore_case() {
+        assert_eq!(alias("Player"), alias("player"));
+        assert_ne!(alias("Player"), alias("Other"));
+        assert_eq!(alias("Player"), alias("Player"));
+        assert!(alias("Player").starts_with("Redacted"));
+    }
+
+    #[test]
+    fn names_are_read_from_paths_and_search_results() {
+        let mut names = player_names(
+            "/overwatch.blizzard.com/en-us/search/account-by-name/play/",
+            r#"[{"battleTag":"Player#1234"},{"battleTag":"Playful#42"},{"other":1}]"#,
+        );
+        names.sort();
+        assert_eq!(names, ["Player", "Playful", "play"]);
+        assert_eq!(
+            player_names("/www.overbuff.com/players/Some-Name-1234/", ""),
+            ["Some-Name"]
+        );
+        assert_eq!(
+            player_names("/overwatch.blizzard.com/en-us/heroes/", ""),
+            [""; 0]
+        );
+    }
+
+    #[test]
+    fn paths_outside_of_the_directory_are_rejected() {
+        let dir = Path::new("/recordings");
+        assert_eq!(
+            recording_path(dir, "/host/career/Player-1234/"),
+            Some(dir.join("host/career/Player-1234/index.html"))
+        );
+        assert_eq!(
+            recording_path(dir, "/host/search"),
+            Some(dir.join("host/search"))
+        );
+        assert_eq!(recording_path(dir, "/host/../../etc/passwd"), None);
+    }
+
+    #[test]
+    fn responses_are_recorded_as_received() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Recorder::new(dir.path()).unwrap();
+        let body = "<h1>Player</h1>";
+        recorder.record(CAREER_URL, StatusCode::OK, &headers(), body);
+
+        let path = "/overwatch.blizzard.com/en-us/career/Player-1234/";
+        let (recorded, recording) = read(dir.path(), path);
+        assert_eq!(recorded, body);
+        assert_eq!(recording.url, CAREER_URL);
+        assert_eq!(recording.status, 200);
+        let headers: Vec<_> = recording.headers.keys().map(String::as_str).collect();
+        assert_eq!(headers, ["content-type", "set-cookie"]);
+    }
+
+    #[test]
+    fn redacted_recordings_contain_no_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = Recorder::new(dir.path()).unwrap().redact(true);
+        recorder.record(
+            "https://overwatch.blizzard.com/en-us/search/account-by-name/play",
+            StatusCode::OK,
+            &HeaderMap::new(),
+            r#"[{"battleTag":"Player#1234"}]"#,
+        );
+        recorder.record(
+            CAREER_URL,
+            StatusCode::OK,
+            &headers(),
+            "<h1>Player</h1><p>Player of the game</p>",
+        );
+
+        let player = alias("Player");
+        let path = format!("/overwatch.blizzard.com/en-us/career/{player}-1234/");
+        let (body, recording) = read(dir.path(), &path);
+        assert_eq!(body, format!("<h1>{player}</h1><p>Player of the game</p>"));
+        assert!(!recording.url.contains("Player"));
+        assert!(!recording.headers.contains_key("set-cookie"));
+
+        let search = format!(
+            "/overwatch.blizzard.com/en-us/search/account-by-name/{}",
+            alias("play")
+        );
+        let (body, _) = read(dir.path(), &search);
+        assert_eq!(body, format!(r#"[{{"battleTag":"{player}#1234"}}]"#));
+        assert!(!dir
+            .path()
+            .join("overwatch.blizzard.com/en-us/career/Player-1234")
+            .exists());
+    }
+}
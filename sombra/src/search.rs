@@ -21,9 +21,8 @@ struct FoundPlayerRaw {
 impl Client {
     #[instrument(level = "debug", skip(self))]
     pub async fn search(&self, name: &str) -> crate::Result<Vec<FoundPlayer>> {
-        let url = "https://overwatch.blizzard.com/en-us/search/account-by-name/";
-        let raw: Vec<FoundPlayerRaw> =
-            serde_json::from_str(&self.get(&format!("{url}{name}")).await?)?;
+        let url = self.upstream.search_url(name);
+        let raw: Vec<FoundPlayerRaw> = serde_json::from_str(&self.get(&url).await?)?;
         raw.into_iter()
             .map(|f| {
                 let namecard = f.namecard.and_then(|id| self.assets.get(&id)?.icon.clone());
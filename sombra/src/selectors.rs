@@ -0,0 +1,378 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde_derive::{Deserialize, Serialize};
+use sombra_types::{Battletag, Group, PlayerProfile, ProfileParts, Role};
+use tl::ParserOptions;
+
+use crate::{heroes::parse_heroes, parse_overbuff, parse_profile, Client, Error};
+
+/// Version of the selector configuration format understood by this build.
+pub const SELECTORS_VERSION: u32 = 1;
+
+const DEFAULT_SELECTORS: &str = include_str!("../selectors.toml");
+
+/// CSS selectors, class names and string mappings the scrapers depend on, so that upstream
+/// markup changes can be followed without a redeploy. The default is compiled in from
+/// `selectors.toml`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Selectors {
+    pub version: u32,
+    pub career: CareerSelectors,
+    pub overbuff: OverbuffSelectors,
+    pub heroes: HeroesSelectors,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CareerSelectors {
+    pub private: String,
+    pub title: String,
+    pub endorsement: String,
+    pub portrait: String,
+    pub masthead: String,
+    pub last_updated_attr: String,
+    pub rank_wrapper: String,
+    pub role_wrapper: String,
+    pub rank: String,
+    pub console_role_icon: String,
+    pub console_role_icon_attr: String,
+    pub pc_role_icon: String,
+    pub pc_role_icon_attr: String,
+    pub view_class: String,
+    pub console_class: String,
+    pub pc_class: String,
+    pub stats_class: String,
+    pub quickplay_class: String,
+    pub competitive_class: String,
+    pub dropdown_class: String,
+    pub stats_container_class: String,
+    pub hero_option_prefix: String,
+    pub stat_item_class: String,
+    pub stat_name_class: String,
+    pub stat_value_class: String,
+    /// Start of the rank image file name
+    pub tiers: BTreeMap<String, Group>,
+    /// Prefix of the role icon file name
+    pub roles: BTreeMap<String, Role>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OverbuffSelectors {
+    pub rank_containers: String,
+    /// Index of the container holding the ranks among all matches of `rank_containers`
+    pub rank_container_index: usize,
+    pub rank: String,
+    pub role_icon: String,
+    pub rank_image: String,
+    pub rank_image_attr: String,
+    /// First word of the rank image alt text
+    pub tiers: BTreeMap<String, Group>,
+    /// Length of the role icon markup
+    pub roles: BTreeMap<String, Role>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeroesSelectors {
+    pub card: String,
+    pub portrait: String,
+    pub portrait_attr: String,
+    pub role_attr: String,
+    pub name_attr: String,
+    pub roles: BTreeMap<String, Role>,
+}
+
+impl Default for Selectors {
+    fn default() -> Self {
+        toml::from_str(DEFAULT_SELECTORS).expect("invalid default selectors")
+    }
+}
+
+impl Selectors {
+    /// Parses and [validates](Self::validate) a configuration.
+    pub fn from_toml(s: &str) -> crate::Result<Self> {
+        let selectors: Self =
+            toml::from_str(s).map_err(|e| Error::Selectors(vec![e.to_string()]))?;
+        selectors.validate()?;
+        Ok(selectors)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> crate::Result<Self> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("selectors are always serializable")
+    }
+
+    /// Checks the version, that every selector can be compiled and that every class name is a
+    /// single class.
+    pub fn validate(&self) -> crate::Result<()> {
+        let mut errors = Vec::new();
+        if self.version != SELECTORS_VERSION {
+            errors.push(format!(
+                "Unsupported version {}, expected {SELECTORS_VERSION}",
+                self.version
+            ));
+        }
+        let career = &self.career;
+        let overbuff = &self.overbuff;
+        let heroes = &self.heroes;
+        let dom = tl::parse("", ParserOptions::new())?;
+        for (name, selector) in [
+            ("career.private", &career.private),
+            ("career.title", &career.title),
+            ("career.endorsement", &career.endorsement),
+            ("career.portrait", &career.portrait),
+            ("career.masthead", &career.masthead),
+            ("career.rank_wrapper", &career.rank_wrapper),
+            ("career.role_wrapper", &career.role_wrapper),
+            ("career.rank", &career.rank),
+            ("career.console_role_icon", &career.console_role_icon),
+            ("career.pc_role_icon", &career.pc_role_icon),
+            ("overbuff.rank_containers", &overbuff.rank_containers),
+            ("overbuff.rank", &overbuff.rank),
+            ("overbuff.role_icon", &overbuff.role_icon),
+            ("overbuff.rank_image", &overbuff.rank_image),
+            ("heroes.card", &heroes.card),
+            ("heroes.portrait", &heroes.portrait),
+        ] {
+            if selector.is_empty() || dom.query_selector(selector).is_none() {
+                errors.push(format!("Invalid selector {name}: {selector:?}"));
+            }
+        }
+        for (name, class) in [
+            ("career.view_class", &career.view_class),
+            ("career.console_class", &career.console_class),
+            ("career.pc_class", &career.pc_class),
+            ("career.stats_class", &career.stats_class),
+            ("career.quickplay_class", &career.quickplay_class),
+            ("career.competitive_class", &career.competitive_class),
+            ("career.dropdown_class", &career.dropdown_class),
+            (
+                "career.stats_container_class",
+                &career.stats_container_class,
+            ),
+            ("career.stat_item_class", &career.stat_item_class),
+            ("career.stat_name_class", &career.stat_name_class),
+            ("career.stat_value_class", &career.stat_value_class),
+        ] {
+            if class.is_empty() || class.contains(|c: char| c.is_whitespace() || c == '.') {
+                errors.push(format!("Invalid class {name}: {class:?}"));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Selectors(errors))
+        }
+    }
+
+    /// Parses the pages saved in `dir/career`, `dir/overbuff` and `dir/heroes` and returns a
+    /// description of every page that failed. Public career pages must yield a hero with stats,
+    /// which is how outdated stat class names show.
+    pub fn check_fixtures(&self, dir: impl AsRef<Path>) -> crate::Result<Vec<String>> {
+        let dir = dir.as_ref();
+        let btag = Battletag::new("Fixture", 1);
+        let mut errors = Vec::new();
+        for path in fixtures(&dir.join("career"))? {
+            let html = fs::read_to_string(&path)?;
+            match parse_profile(&btag, &html, ProfileParts::ALL, self) {
+                Ok(profile) if !profile.visibility.is_private() && !has_stats(&profile) => {
+                    errors.push(format!("{}: no hero stats", path.display()));
+                }
+                Ok(_) => {}
+                Err(e) => errors.push(format!("{}: {e}", path.display())),
+            }
+        }
+        for path in fixtures(&dir.join("overbuff"))? {
+            if let Err(e) = parse_overbuff(&fs::read_to_string(&path)?, self) {
+                errors.push(format!("{}: {e}", path.display()));
+            }
+        }
+        for path in fixtures(&dir.join("heroes"))? {
+            match parse_heroes(&fs::read_to_string(&path)?, self) {
+                Ok(heroes) if heroes.is_empty() => {
+                    errors.push(format!("{}: no heroes", path.display()));
+                }
+                Ok(_) => {}
+                Err(e) => errors.push(format!("{}: {e}", path.display())),
+            }
+        }
+        Ok(errors)
+    }
+}
+
+fn has_stats(profile: &PlayerProfile) -> bool {
+    profile
+        .tables()
+        .flat_map(HashMap::values)
+        .any(|hero| !hero.stats.is_empty())
+}
+
+/// The HTML files in `dir`, which may not exist.
+fn fixtures(dir: &Path) -> crate::Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut pages = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "html") {
+            pages.push(path);
+        }
+    }
+    pages.sort();
+    Ok(pages)
+}
+
+impl Client {
+    pub fn selectors(&self) -> Arc<Selectors> {
+        self.selectors.read().clone()
+    }
+
+    /// Replaces the selectors used by every following request.
+    pub fn set_selectors(&self, selectors: Selectors) {
+        *self.selectors.write() = Arc::new(selectors);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Upstream;
+
+    fn fixtures_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures")
+    }
+
+    #[test]
+    fn default_selectors_round_trip() {
+        let selectors = Selectors::default();
+        selectors.validate().unwrap();
+        assert_eq!(
+            Selectors::from_toml(&selectors.to_toml()).unwrap(),
+            selectors
+        );
+    }
+
+    #[test]
+    fn unsupported_versions_are_rejected() {
+        let selectors = Selectors {
+            version: SELECTORS_VERSION + 1,
+            ..Selectors::default()
+        };
+        let Err(Error::Selectors(errors)) = Selectors::from_toml(&selectors.to_toml()) else {
+            panic!("version {} was accepted", selectors.version);
+        };
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("Unsupported version"));
+    }
+
+    #[test]
+    fn invalid_selectors_are_all_reported() {
+        let mut selectors = Selectors::default();
+        selectors.career.title = String::new();
+        selectors.heroes.card = String::new();
+        let Err(Error::Selectors(errors)) = selectors.validate() else {
+            panic!("empty selectors were accepted");
+        };
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("career.title"));
+        assert!(errors[1].contains("heroes.card"));
+    }
+
+    #[test]
+    fn invalid_class_names_are_reported() {
+        let mut selectors = Selectors::default();
+        selectors.career.stat_item_class = ".stat-item".to_owned();
+        selectors.career.stat_value_class = "stat value".to_owned();
+        selectors.career.stat_name_class = String::new();
+        let Err(Error::Selectors(errors)) = selectors.validate() else {
+            panic!("invalid class names were accepted");
+        };
+        assert_eq!(errors.len(), 3, "{errors:?}");
+        for (error, name) in errors.iter().zip(["item", "name", "value"]) {
+            assert!(
+                error.contains(&format!("career.stat_{name}_class")),
+                "{error}"
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        assert!(matches!(
+            Selectors::from_toml("version = 1"),
+            Err(Error::Selectors(_))
+        ));
+    }
+
+    #[test]
+    fn selectors_are_loaded_from_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("selectors.toml");
+        let mut selectors = Selectors::default();
+        selectors.career.stat_item_class = "stat-row".to_owned();
+        fs::write(&path, selectors.to_toml()).unwrap();
+        assert_eq!(Selectors::load(&path).unwrap(), selectors);
+        assert!(Selectors::load(dir.path().join("missing.toml")).is_err());
+    }
+
+    #[test]
+    fn default_selectors_parse_the_fixtures() {
+        let errors = Selectors::default().check_fixtures(fixtures_dir()).unwrap();
+        assert!(errors.is_empty(), "{errors:?}");
+    }
+
+    #[test]
+    fn outdated_selectors_fail_the_fixtures() {
+        let mut selectors = Selectors::default();
+        selectors.career.view_class = "Profile-layout".to_owned();
+        let errors = selectors.check_fixtures(fixtures_dir()).unwrap();
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(errors[0].contains("public.html: no hero stats"));
+    }
+
+    #[test]
+    fn outdated_stat_classes_fail_the_fixtures() {
+        let classes: [fn(&mut CareerSelectors) -> &mut String; 3] = [
+            |career| &mut career.stat_item_class,
+            |career| &mut career.stat_name_class,
+            |career| &mut career.stat_value_class,
+        ];
+        for class in classes {
+            let mut selectors = Selectors::default();
+            *class(&mut selectors.career) = "stat-row".to_owned();
+            selectors.validate().unwrap();
+            let errors = selectors.check_fixtures(fixtures_dir()).unwrap();
+            assert_eq!(errors.len(), 1, "{errors:?}");
+            assert!(errors[0].contains("public.html"), "{errors:?}");
+        }
+    }
+
+    #[test]
+    fn missing_fixture_directories_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(Selectors::default()
+            .check_fixtures(dir.path())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn reloaded_selectors_replace_the_current_ones() {
+        let client = Client::unfetched(Upstream::default());
+        assert_eq!(*client.selectors(), Selectors::default());
+        let before = client.selectors();
+
+        let mut selectors = Selectors::default();
+        selectors.career.stat_item_class = "stat-row".to_owned();
+        client.set_selectors(selectors.clone());
+        assert_eq!(*client.selectors(), selectors);
+        assert_eq!(*before, Selectors::default());
+    }
+}
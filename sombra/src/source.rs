@@ -0,0 +1,180 @@
+use std::{fmt::Debug, sync::Arc};
+
+use futures::future::BoxFuture;
+use sombra_types::{Battletag, SourceData};
+
+use crate::{Client, Error};
+
+/// Site that knows the ranks and hero stats of players, such as the Blizzard career page,
+/// Overbuff or an internal tracker. Sources are registered on a [`Client`] with a priority,
+/// which decides between ranks that were observed at the same time.
+pub trait DataSource: Send + Sync {
+    /// Unique name, reported as the source of ranks and used in cache keys
+    fn name(&self) -> &str;
+
+    /// Fetches what the source knows about `btag`. [`Client::get`] sends requests with the same
+    /// client as the built-in sources.
+    fn fetch<'a>(
+        &'a self,
+        client: &'a Client,
+        btag: &'a Battletag,
+    ) -> BoxFuture<'a, crate::Result<SourceData>>;
+}
+
+/// A [`DataSource`] and its priority. Higher priorities win.
+#[derive(Clone)]
+pub struct RegisteredSource {
+    pub source: Arc<dyn DataSource>,
+    pub priority: i32,
+}
+
+impl Debug for RegisteredSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RegisteredSource")
+            .field("name", &self.source.name())
+            .field("priority", &self.priority)
+            .finish()
+    }
+}
+
+impl Client {
+    /// Adds `source`, replacing any source with the same name.
+    pub fn register_source(&self, source: impl DataSource + 'static, priority: i32) {
+        let mut sources = self.sources.write();
+        sources.retain(|s| s.source.name() != source.name());
+        sources.push(RegisteredSource {
+            source: Arc::new(source),
+            priority,
+        });
+        sources.sort_by_key(|s| std::cmp::Reverse(s.priority));
+    }
+
+    /// Removes the source called `name`. Returns whether it was registered.
+    pub fn unregister_source(&self, name: &str) -> bool {
+        let mut sources = self.sources.write();
+        let len = sources.len();
+        sources.retain(|s| s.source.name() != name);
+        sources.len() != len
+    }
+
+    /// The registered sources, highest priority first.
+    pub fn sources(&self) -> Vec<RegisteredSource> {
+        self.sources.read().clone()
+    }
+
+    /// Fetches `btag` from the source called `name`.
+    pub async fn source(&self, name: &str, btag: &Battletag) -> crate::Result<SourceData> {
+        let source = self
+            .sources
+            .read()
+            .iter()
+            .find(|s| s.source.name() == name)
+            .map(|s| s.source.clone())
+            .ok_or_else(|| Error::UnknownSource(name.to_owned()))?;
+        source.fetch(self, btag).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use futures::FutureExt;
+
+    use super::*;
+    use crate::{CareerPageSource, OverbuffSource, Upstream};
+
+    /// Source called `name` that knows nothing, counting how often it is asked.
+    struct Named(&'static str, Arc<AtomicU32>);
+
+    impl Named {
+        fn new(name: &'static str) -> Self {
+            Self(name, Arc::default())
+        }
+    }
+
+    impl DataSource for Named {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn fetch<'a>(
+            &'a self,
+            _: &'a Client,
+            _: &'a Battletag,
+        ) -> BoxFuture<'a, crate::Result<SourceData>> {
+            self.1.fetch_add(1, Ordering::SeqCst);
+            let data = SourceData {
+                ranks: Vec::new(),
+                heroes: Vec::new(),
+                last_updated: None,
+            };
+            async move { Ok(data) }.boxed()
+        }
+    }
+
+    fn names(client: &Client) -> Vec<(String, i32)> {
+        client
+            .sources()
+            .iter()
+            .map(|s| (s.source.name().to_owned(), s.priority))
+            .collect()
+    }
+
+    #[test]
+    fn sources_are_ordered_by_priority() {
+        let client = Client::unfetched(Upstream::default());
+        client.register_source(Named::new("tracker"), 50);
+        client.register_source(Named::new("fallback"), -10);
+        let expected = [
+            (CareerPageSource::NAME, CareerPageSource::PRIORITY),
+            ("tracker", 50),
+            (OverbuffSource::NAME, OverbuffSource::PRIORITY),
+            ("fallback", -10),
+        ];
+        let expected: Vec<_> = expected.iter().map(|(n, p)| (n.to_string(), *p)).collect();
+        assert_eq!(names(&client), expected);
+    }
+
+    #[test]
+    fn registering_a_name_again_replaces_the_source() {
+        let client = Client::unfetched(Upstream::default());
+        client.register_source(Named::new("tracker"), 50);
+        client.register_source(Named::new("tracker"), 200);
+        let names = names(&client);
+        assert_eq!(names[0], ("tracker".to_owned(), 200));
+        assert_eq!(names.iter().filter(|(n, _)| n == "tracker").count(), 1);
+    }
+
+    #[test]
+    fn unregistered_sources_are_removed() {
+        let client = Client::unfetched(Upstream::default());
+        client.register_source(Named::new("tracker"), 50);
+        assert!(client.unregister_source("tracker"));
+        assert!(!client.unregister_source("tracker"));
+        assert!(client.unregister_source(OverbuffSource::NAME));
+        assert_eq!(
+            names(&client),
+            [(
+                CareerPageSource::NAME.to_owned(),
+                CareerPageSource::PRIORITY
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn sources_are_fetched_by_name() {
+        let client = Client::unfetched(Upstream::default());
+        let tracker = Named::new("tracker");
+        let fetches = tracker.1.clone();
+        client.register_source(tracker, 50);
+        client.register_source(Named::new("other"), 60);
+        let btag = Battletag::new("Player", 1234);
+        client.source("tracker", &btag).await.unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        client.unregister_source("tracker");
+        let error = client.source("tracker", &btag).await.unwrap_err();
+        assert!(matches!(error, Error::UnknownSource(name) if name == "tracker"));
+    }
+}
@@ -0,0 +1,257 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use chrono::{DateTime, Utc};
+use sombra_types::CacheKind;
+
+use super::{CacheEntry, CacheKey, CacheStore, CacheValue, Lifespans};
+
+/// Store keeping one JSON file per entry below `dir`.
+///
+/// Files are named after their key and modified at the insertion time of their entry, so entries
+/// are listed without reading them. Files are replaced atomically, so several processes on the
+/// same host can share a directory.
+#[derive(Debug)]
+pub struct DiskStore {
+    dir: PathBuf,
+    lifespans: Lifespans,
+}
+
+impl DiskStore {
+    pub fn open(dir: impl Into<PathBuf>, lifespans: Lifespans) -> std::io::Result<Self> {
+        let dir = dir.into();
+        for kind in [CacheKind::Profile, CacheKind::Overbuff, CacheKind::Search] {
+            fs::create_dir_all(dir.join(kind_dir(kind)))?;
+        }
+        Ok(Self { dir, lifespans })
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, key: &CacheKey) -> PathBuf {
+        let file: String = url::form_urlencoded::byte_serialize(key.key.as_bytes()).collect();
+        self.dir.join(kind_dir(key.kind)).join(file + ".json")
+    }
+
+    /// Writes an entry serialized as JSON.
+    pub(super) fn write(&self, key: &CacheKey, entry: CacheEntry<&[u8]>) {
+        let path = self.path(key);
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        let write = || {
+            let mut file = File::create(&tmp)?;
+            file.write_all(entry.value)?;
+            file.set_modified(SystemTime::from(entry.inserted))?;
+            fs::rename(&tmp, &path)
+        };
+        if let Err(error) = write() {
+            tracing::warn!(?path, %error, "Could not write cache entry");
+        }
+    }
+
+    /// Reads every entry that has not expired yet, leaving values undecoded.
+    pub(super) fn load(&self) -> Vec<(CacheKey, CacheEntry<serde_json::Value>)> {
+        self.files()
+            .into_iter()
+            .filter_map(|(key, _)| Some((key.clone(), self.read(&key)?)))
+            .collect()
+    }
+
+    /// Only files that are not valid entries are deleted, values of another type are kept.
+    fn read<V: CacheValue>(&self, key: &CacheKey) -> Option<CacheEntry<V>> {
+        let path = self.path(key);
+        let json = fs::read(&path).ok()?;
+        let entry: CacheEntry<serde_json::Value> = match serde_json::from_slice(&json) {
+            Ok(entry) => entry,
+            Err(error) => {
+                tracing::warn!(?path, %error, "Discarding unreadable cache entry");
+                let _ = fs::remove_file(&path);
+                return None;
+            }
+        };
+        if entry.is_expired(self.lifespans.get(key.kind)) {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+        match serde_json::from_value::<V>(entry.value) {
+            Ok(value) => Some(CacheEntry {
+                value,
+                inserted: entry.inserted,
+            }),
+            Err(error) => {
+                tracing::debug!(?path, %error, "Cache entry has another type");
+                None
+            }
+        }
+    }
+
+    /// Lists the stored keys with their files.
+    fn files(&self) -> Vec<(CacheKey, fs::DirEntry)> {
+        let mut files = Vec::new();
+        for kind in [CacheKind::Profile, CacheKind::Overbuff, CacheKind::Search] {
+            let Ok(dir) = fs::read_dir(self.dir.join(kind_dir(kind))) else {
+                continue;
+            };
+            for file in dir.filter_map(std::result::Result::ok) {
+                let name = file.file_name();
+                let Some(encoded) = name.to_str().and_then(|n| n.strip_suffix(".json")) else {
+                    continue;
+                };
+                let Some((key, _)) = url::form_urlencoded::parse(encoded.as_bytes()).next() else {
+                    continue;
+                };
+                let key = CacheKey {
+                    kind,
+                    key: key.into_owned(),
+                };
+                files.push((key, file));
+            }
+        }
+        files
+    }
+}
+
+impl CacheStore for DiskStore {
+    fn get<V: CacheValue>(&self, key: &CacheKey) -> Option<CacheEntry<Arc<V>>> {
+        Some(self.read(key)?.map(Arc::new))
+    }
+
+    fn set<V: CacheValue>(&self, key: CacheKey, value: Arc<V>) -> Option<CacheKey> {
+        let entry = CacheEntry::new(&*value);
+        match serde_json::to_vec(&entry) {
+            Ok(json) => self.write(&key, entry.map(|_| &*json)),
+            Err(error) => tracing::warn!(?key, %error, "Could not serialize cache entry"),
+        }
+        None
+    }
+
+    fn invalidate(&self, key: &CacheKey) -> bool {
+        fs::remove_file(self.path(key)).is_ok()
+    }
+
+    fn clear(&self) {
+        for kind in [CacheKind::Profile, CacheKind::Overbuff, CacheKind::Search] {
+            let dir = self.dir.join(kind_dir(kind));
+            let _ = fs::remove_dir_all(&dir);
+            if let Err(error) = fs::create_dir_all(&dir) {
+                tracing::warn!(?dir, %error, "Could not recreate cache directory");
+            }
+        }
+    }
+
+    fn lifespan(&self, kind: CacheKind) -> Duration {
+        self.lifespans.get(kind)
+    }
+
+    fn entries(&self) -> Vec<(CacheKey, DateTime<Utc>)> {
+        self.files()
+            .into_iter()
+            .filter_map(|(key, file)| {
+                let inserted = DateTime::<Utc>::from(file.metadata().ok()?.modified().ok()?);
+                let age = (Utc::now() - inserted).to_std().unwrap_or_default();
+                (age < self.lifespans.get(key.kind)).then_some((key, inserted))
+            })
+            .collect()
+    }
+
+    fn keys(&self) -> Vec<CacheKey> {
+        self.files().into_iter().map(|(key, _)| key).collect()
+    }
+}
+
+const fn kind_dir(kind: CacheKind) -> &'static str {
+    match kind {
+        CacheKind::Profile => "profile",
+        CacheKind::Overbuff => "overbuff",
+        CacheKind::Search => "search",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(lifespan: u64) -> (tempfile::TempDir, DiskStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let lifespans = Lifespans::new(lifespan, lifespan, lifespan);
+        let store = DiskStore::open(dir.path(), lifespans).unwrap();
+        (dir, store)
+    }
+
+    #[test]
+    fn round_trip() {
+        let (_dir, store) = store(60);
+        let key = CacheKey::search("Player#1234?x");
+        assert_eq!(store.set(key.clone(), Arc::new(vec![1, 2, 3])), None);
+        let entry = store.get::<Vec<u32>>(&key).unwrap();
+        assert_eq!(*entry.value, [1, 2, 3]);
+        assert_eq!(store.keys(), std::slice::from_ref(&key));
+        assert!(store.invalidate(&key));
+        assert!(store.get::<Vec<u32>>(&key).is_none());
+        assert!(!store.invalidate(&key));
+    }
+
+    #[test]
+    fn entries_are_listed_with_their_insertion_time() {
+        let (_dir, store) = store(60 * 60);
+        let key = CacheKey::search("player");
+        let inserted = Utc::now() - chrono::Duration::minutes(10);
+        let entry = CacheEntry {
+            value: &b"\"value\""[..],
+            inserted,
+        };
+        store.write(&key, entry);
+        let entries = store.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].0, key);
+        assert!((entries[0].1 - inserted).num_milliseconds().abs() < 1000);
+    }
+
+    #[test]
+    fn expired_entries_are_missing() {
+        let (_dir, store) = store(0);
+        let key = CacheKey::search("player");
+        store.set(key.clone(), Arc::new(1));
+        assert!(store.entries().is_empty());
+        assert_eq!(store.keys(), std::slice::from_ref(&key));
+        assert!(store.get::<u32>(&key).is_none());
+        assert!(store.keys().is_empty());
+    }
+
+    #[test]
+    fn unreadable_entries_are_discarded() {
+        let (_dir, store) = store(60);
+        let key = CacheKey::search("player");
+        fs::write(store.path(&key), b"{\"value\": 1").unwrap();
+        assert!(store.get::<u32>(&key).is_none());
+        assert!(store.keys().is_empty());
+    }
+
+    #[test]
+    fn entries_of_another_type_are_kept() {
+        let (_dir, store) = store(60);
+        let key = CacheKey::search("player");
+        store.set(key.clone(), Arc::new("text".to_owned()));
+        assert!(store.get::<u32>(&key).is_none());
+        assert_eq!(store.keys(), std::slice::from_ref(&key));
+        assert_eq!(*store.get::<String>(&key).unwrap().value, "text");
+    }
+
+    #[test]
+    fn clear_removes_every_entry() {
+        let (_dir, store) = store(60);
+        store.set(CacheKey::search("a"), Arc::new(1));
+        store.set(CacheKey::search("b"), Arc::new(2));
+        store.clear();
+        assert!(store.keys().is_empty());
+        store.set(CacheKey::search("c"), Arc::new(3));
+        assert_eq!(store.keys().len(), 1);
+    }
+}
@@ -0,0 +1,329 @@
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use cached::{Cached, SizedCache};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use sombra_types::CacheKind;
+
+use super::{CacheEntry, CacheKey, CacheStore, CacheValue, DiskStore, Lifespans};
+
+pub const DEFAULT_CAPACITY: usize = 4096;
+
+/// In-memory store that evicts the least recently used entry once `capacity` is reached.
+///
+/// A persistent store additionally writes its entries behind to a [`DiskStore`] on
+/// [`flush`](CacheStore::flush) and reloads them, with their original insertion time, when opened.
+/// Entries are only written on `flush` and when the store is dropped, so another store sharing the
+/// directory sees them only after that, e.g. up to a minute late with the server's flush interval.
+#[derive(Debug)]
+pub struct LruStore {
+    cache: Mutex<SizedCache<CacheKey, CacheEntry<AnyValue>>>,
+    capacity: usize,
+    lifespans: Lifespans,
+    disk: Option<DiskStore>,
+    /// Serialized entries waiting to be written to `disk`
+    pending: Mutex<HashMap<CacheKey, CacheEntry<Vec<u8>>>>,
+}
+
+struct AnyValue(Arc<dyn Any + Send + Sync>);
+
+/// Value loaded from disk that has not been requested with a concrete type yet.
+struct Persisted(serde_json::Value);
+
+impl LruStore {
+    #[must_use]
+    pub fn new(lifespans: Lifespans, capacity: usize) -> Self {
+        Self {
+            cache: Mutex::new(SizedCache::with_size(capacity)),
+            capacity,
+            lifespans,
+            disk: None,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn persistent(
+        lifespans: Lifespans,
+        capacity: usize,
+        dir: impl Into<PathBuf>,
+    ) -> std::io::Result<Self> {
+        let disk = DiskStore::open(dir, lifespans)?;
+        let mut entries = disk.load();
+        entries.sort_by_key(|(_, entry)| entry.inserted);
+        let mut cache = SizedCache::with_size(capacity);
+        for (key, entry) in entries {
+            let value = AnyValue(Arc::new(Persisted(entry.value)));
+            cache.cache_set(
+                key,
+                CacheEntry {
+                    value,
+                    inserted: entry.inserted,
+                },
+            );
+        }
+        tracing::info!(entries = cache.cache_size(), dir = ?disk.dir(), "Loaded persisted cache");
+        Ok(Self {
+            cache: Mutex::new(cache),
+            capacity,
+            lifespans,
+            disk: Some(disk),
+            pending: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn get_memory<V: CacheValue>(&self, key: &CacheKey) -> Option<CacheEntry<Arc<V>>> {
+        let mut cache = self.cache.lock();
+        let entry = cache.cache_get_mut(key)?;
+        if entry.is_expired(self.lifespans.get(key.kind)) {
+            cache.cache_remove(key);
+            return None;
+        }
+        if let Some(Persisted(json)) = entry.value.0.downcast_ref::<Persisted>() {
+            match V::deserialize(json) {
+                Ok(value) => entry.value = AnyValue(Arc::new(value)),
+                Err(error) => {
+                    tracing::warn!(?key, %error, "Discarding unreadable cache entry");
+                    cache.cache_remove(key);
+                    return None;
+                }
+            }
+        }
+        Some(CacheEntry {
+            value: entry.value.0.clone().downcast::<V>().ok()?,
+            inserted: entry.inserted,
+        })
+    }
+
+    fn insert(&self, key: CacheKey, entry: CacheEntry<AnyValue>) -> Option<CacheKey> {
+        let mut cache = self.cache.lock();
+        let victim = (cache.cache_size() >= self.capacity)
+            .then(|| cache.get_order().iter().last().map(|(key, _)| key.clone()))
+            .flatten();
+        let replaced = cache.cache_set(key, entry).is_some();
+        // the least recently used entry is only dropped when a new key was added
+        victim.filter(|_| !replaced)
+    }
+}
+
+impl CacheStore for LruStore {
+    fn get<V: CacheValue>(&self, key: &CacheKey) -> Option<CacheEntry<Arc<V>>> {
+        if let Some(entry) = self.get_memory(key) {
+            return Some(entry);
+        }
+        // entries may have been evicted from memory or written by another process
+        let entry = self.disk.as_ref()?.get::<V>(key)?;
+        self.insert(
+            key.clone(),
+            CacheEntry {
+                value: AnyValue(entry.value.clone()),
+                inserted: entry.inserted,
+            },
+        );
+        Some(entry)
+    }
+
+    fn set<V: CacheValue>(&self, key: CacheKey, value: Arc<V>) -> Option<CacheKey> {
+        let entry = CacheEntry::new(value);
+        if self.disk.is_some() {
+            match serde_json::to_vec(&entry.as_ref().map(|v| &**v)) {
+                Ok(json) => {
+                    let json = CacheEntry {
+                        value: json,
+                        inserted: entry.inserted,
+                    };
+                    self.pending.lock().insert(key.clone(), json);
+                }
+                Err(error) => tracing::warn!(?key, %error, "Could not serialize cache entry"),
+            }
+        }
+        let entry = CacheEntry {
+            value: AnyValue(entry.value),
+            inserted: entry.inserted,
+        };
+        self.insert(key, entry)
+    }
+
+    fn invalidate(&self, key: &CacheKey) -> bool {
+        let pending = self.pending.lock().remove(key).is_some();
+        let disk = self.disk.as_ref().is_some_and(|disk| disk.invalidate(key));
+        let memory = self.cache.lock().cache_remove(key).is_some();
+        pending || disk || memory
+    }
+
+    fn clear(&self) {
+        self.pending.lock().clear();
+        if let Some(disk) = &self.disk {
+            disk.clear();
+        }
+        self.cache.lock().cache_clear();
+    }
+
+    fn lifespan(&self, kind: CacheKind) -> Duration {
+        self.lifespans.get(kind)
+    }
+
+    fn entries(&self) -> Vec<(CacheKey, DateTime<Utc>)> {
+        let mut entries: HashMap<_, _> = self
+            .cache
+            .lock()
+            .get_order()
+            .iter()
+            .filter(|(key, entry)| !entry.is_expired(self.lifespans.get(key.kind)))
+            .map(|(key, entry)| (key.clone(), entry.inserted))
+            .collect();
+        if let Some(disk) = &self.disk {
+            for (key, inserted) in disk.entries() {
+                entries.entry(key).or_insert(inserted);
+            }
+        }
+        entries.into_iter().collect()
+    }
+
+    fn keys(&self) -> Vec<CacheKey> {
+        let mut keys: HashSet<_> = self
+            .cache
+            .lock()
+            .get_order()
+            .iter()
+            .map(|(key, _)| key.clone())
+            .collect();
+        keys.extend(self.pending.lock().keys().cloned());
+        if let Some(disk) = &self.disk {
+            keys.extend(disk.keys());
+        }
+        keys.into_iter().collect()
+    }
+
+    fn flush(&self) {
+        let Some(disk) = &self.disk else {
+            return;
+        };
+        let pending = std::mem::take(&mut *self.pending.lock());
+        if !pending.is_empty() {
+            tracing::debug!(entries = pending.len(), "Flushing cache");
+        }
+        for (key, entry) in pending {
+            disk.write(&key, entry.as_ref().map(Vec::as_slice));
+        }
+    }
+}
+
+impl Drop for LruStore {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+impl Debug for AnyValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AnyValue")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let store = LruStore::new(Lifespans::new(60, 60, 60), 2);
+        let (a, b, c) = (
+            CacheKey::search("a"),
+            CacheKey::search("b"),
+            CacheKey::search("c"),
+        );
+        assert_eq!(store.set(a.clone(), Arc::new(1_u32)), None);
+        assert_eq!(store.set(b.clone(), Arc::new(2_u32)), None);
+        assert!(store.get::<u32>(&a).is_some());
+        assert_eq!(store.set(c.clone(), Arc::new(3_u32)), Some(b.clone()));
+        assert!(store.get::<u32>(&b).is_none());
+        assert_eq!(store.get::<u32>(&a).unwrap().value, Arc::new(1_u32));
+        // replacing an entry does not evict another one
+        assert_eq!(store.set(c.clone(), Arc::new(4_u32)), None);
+        assert_eq!(store.get::<u32>(&c).unwrap().value, Arc::new(4_u32));
+    }
+
+    #[test]
+    fn expired_entries_are_missing() {
+        let store = LruStore::new(Lifespans::new(60, 60, 0), 2);
+        let key = CacheKey::search("a");
+        store.set(key.clone(), Arc::new(1_u32));
+        assert!(store.get::<u32>(&key).is_none());
+        assert!(store.entries().is_empty());
+    }
+
+    #[test]
+    fn values_keep_their_type() {
+        let store = LruStore::new(Lifespans::new(60, 60, 60), 2);
+        let key = CacheKey::search("a");
+        store.set(key.clone(), Arc::new(1_u32));
+        assert!(store.get::<String>(&key).is_none());
+        assert!(store.get::<u32>(&key).is_some());
+    }
+
+    #[test]
+    fn persistent_store_reloads_flushed_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let lifespans = Lifespans::new(60, 60, 60);
+        let key = CacheKey::search("a");
+        let inserted = {
+            let store = LruStore::persistent(lifespans, 2, dir.path()).unwrap();
+            store.set(key.clone(), Arc::new(vec![1_u32, 2]));
+            store.flush();
+            store.get::<Vec<u32>>(&key).unwrap().inserted
+        };
+        let store = LruStore::persistent(lifespans, 2, dir.path()).unwrap();
+        assert_eq!(store.keys(), std::slice::from_ref(&key));
+        let entry = store.get::<Vec<u32>>(&key).unwrap();
+        assert_eq!(*entry.value, [1, 2]);
+        assert_eq!(entry.inserted, inserted);
+    }
+
+    #[test]
+    fn persistent_store_flushes_on_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        let lifespans = Lifespans::new(60, 60, 60);
+        let key = CacheKey::search("a");
+        LruStore::persistent(lifespans, 2, dir.path())
+            .unwrap()
+            .set(key.clone(), Arc::new(1_u32));
+        let store = LruStore::persistent(lifespans, 2, dir.path()).unwrap();
+        assert!(store.get::<u32>(&key).is_some());
+    }
+
+    #[test]
+    fn persistent_store_skips_expired_and_invalidated_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (CacheKey::search("a"), CacheKey::search("b"));
+        {
+            let store = LruStore::persistent(Lifespans::new(60, 60, 60), 2, dir.path()).unwrap();
+            store.set(a.clone(), Arc::new(1_u32));
+            store.set(b.clone(), Arc::new(2_u32));
+            store.flush();
+            assert!(store.invalidate(&a));
+        }
+        let store = LruStore::persistent(Lifespans::new(60, 60, 60), 2, dir.path()).unwrap();
+        assert!(store.get::<u32>(&a).is_none());
+        assert!(store.get::<u32>(&b).is_some());
+        let store = LruStore::persistent(Lifespans::new(60, 60, 0), 2, dir.path()).unwrap();
+        assert!(store.get::<u32>(&b).is_none());
+    }
+
+    #[test]
+    fn persistent_store_reads_entries_evicted_from_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LruStore::persistent(Lifespans::new(60, 60, 60), 1, dir.path()).unwrap();
+        let (a, b) = (CacheKey::search("a"), CacheKey::search("b"));
+        store.set(a.clone(), Arc::new(1_u32));
+        store.set(b.clone(), Arc::new(2_u32));
+        store.flush();
+        assert_eq!(store.get::<u32>(&a).unwrap().value, Arc::new(1));
+    }
+}
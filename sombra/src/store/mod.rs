@@ -0,0 +1,163 @@
+mod disk;
+mod memory;
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use sombra_types::{Battletag, CacheKind, ProfileParts};
+
+use crate::OverbuffSource;
+
+pub use disk::*;
+pub use memory::*;
+
+/// Storage backend used by [`CachedClient`](crate::CachedClient).
+///
+/// Entries older than the lifespan of their [`CacheKind`] are treated as missing.
+pub trait CacheStore: Send + Sync {
+    fn get<V: CacheValue>(&self, key: &CacheKey) -> Option<CacheEntry<Arc<V>>>;
+    /// Returns the key of an entry that was evicted to make room.
+    fn set<V: CacheValue>(&self, key: CacheKey, value: Arc<V>) -> Option<CacheKey>;
+    fn invalidate(&self, key: &CacheKey) -> bool;
+    fn clear(&self);
+    fn lifespan(&self, kind: CacheKind) -> Duration;
+    /// Lists the keys of all entries that have not expired, with their insertion time.
+    fn entries(&self) -> Vec<(CacheKey, DateTime<Utc>)>;
+    /// Lists the keys of all entries, including expired ones that have not been removed yet,
+    /// without reading them.
+    fn keys(&self) -> Vec<CacheKey>;
+
+    /// Writes entries that are only held in memory to persistent storage.
+    fn flush(&self) {}
+}
+
+pub trait CacheValue: serde::Serialize + DeserializeOwned + Send + Sync + 'static {}
+
+impl<T: serde::Serialize + DeserializeOwned + Send + Sync + 'static> CacheValue for T {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CacheKey {
+    pub kind: CacheKind,
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry<V> {
+    pub value: V,
+    pub inserted: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lifespans {
+    pub profile: Duration,
+    pub overbuff: Duration,
+    pub search: Duration,
+}
+
+impl CacheKey {
+    /// Profiles with only some parts parsed are cached separately from full ones.
+    #[must_use]
+    pub fn profile(btag: &Battletag, parts: ProfileParts) -> Self {
+        let key = if parts == ProfileParts::ALL {
+            format!("{btag:#}")
+        } else {
+            format!("{btag:#}?{parts}")
+        };
+        Self {
+            kind: CacheKind::Profile,
+            key,
+        }
+    }
+
+    #[must_use]
+    pub fn overbuff(btag: &Battletag) -> Self {
+        Self::source(btag, OverbuffSource::NAME)
+    }
+
+    /// Data of a third-party source. Overbuff keeps the plain battletag as its key.
+    #[must_use]
+    pub fn source(btag: &Battletag, name: &str) -> Self {
+        let key = if name == OverbuffSource::NAME {
+            format!("{btag:#}")
+        } else {
+            format!("{btag:#}@{name}")
+        };
+        Self {
+            kind: CacheKind::Overbuff,
+            key,
+        }
+    }
+
+    #[must_use]
+    pub fn search(name: &str) -> Self {
+        Self {
+            kind: CacheKind::Search,
+            key: name.to_owned(),
+        }
+    }
+
+    /// Whether this is a profile or data source entry of `btag`.
+    #[must_use]
+    pub fn is_player(&self, btag: &Battletag) -> bool {
+        let btag = format!("{btag:#}");
+        self.kind != CacheKind::Search
+            && self
+                .key
+                .strip_prefix(&btag)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['?', '@']))
+    }
+}
+
+impl<V> CacheEntry<V> {
+    pub fn new(value: V) -> Self {
+        Self {
+            value,
+            inserted: Utc::now(),
+        }
+    }
+
+    pub const fn as_ref(&self) -> CacheEntry<&V> {
+        CacheEntry {
+            value: &self.value,
+            inserted: self.inserted,
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(V) -> U) -> CacheEntry<U> {
+        CacheEntry {
+            value: f(self.value),
+            inserted: self.inserted,
+        }
+    }
+
+    #[must_use]
+    pub fn age(&self) -> Duration {
+        (Utc::now() - self.inserted).to_std().unwrap_or_default()
+    }
+
+    pub fn is_expired(&self, lifespan: Duration) -> bool {
+        self.age() >= lifespan
+    }
+}
+
+impl Lifespans {
+    #[must_use]
+    pub const fn new(profile: u64, overbuff: u64, search: u64) -> Self {
+        Self {
+            profile: Duration::from_secs(profile),
+            overbuff: Duration::from_secs(overbuff),
+            search: Duration::from_secs(search),
+        }
+    }
+
+    #[must_use]
+    pub const fn get(&self, kind: CacheKind) -> Duration {
+        match kind {
+            CacheKind::Profile => self.profile,
+            CacheKind::Overbuff => self.overbuff,
+            CacheKind::Search => self.search,
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+
+use sombra_types::{
+    Battletag, FoundPlayer, Hero, HeroStats, Mode, Platform, PlayStats, PlayerProfile,
+    PlayerSummary, ProfileParts, ProfileVisibility, ReconciledRanks, Role, RoleStats, Stat,
+    TopHero,
+};
+
+use crate::{reconcile_ranks, CacheStore, Cached, CachedClient};
+
+const TOP_HEROES: usize = 10;
+const HEADLINE_STATS: [&str; 3] = ["Time Played", "Win Percentage", "Weapon Accuracy"];
+
+impl<S: CacheStore + 'static> CachedClient<S> {
+    /// Merges the search result and career page, reconciling its ranks with those of the other
+    /// registered data sources if it is private or has none. The summary is as fresh as the
+    /// oldest of them. Only a missing search result is an error.
+    pub async fn summary(
+        &self,
+        btag: &Battletag,
+        platform: Platform,
+        mode: Mode,
+    ) -> crate::Result<Cached<PlayerSummary>> {
+        let found = self.found(btag).await?;
+        let parts = ProfileParts {
+            ranks: true,
+            ..ProfileParts::table(platform, mode)
+        };
+        let observations = self.observe_ranks_fallback(btag, parts).await;
+        let profile = observations.profile.ok().map(|p| p.value);
+        let cached = observations.cached.merge(&found);
+
+        Ok(cached.map(|()| {
+            summarize(
+                found.value,
+                profile.as_deref(),
+                reconcile_ranks(observations.ranks, &observations.sources),
+                self.heroes(),
+                platform,
+                mode,
+            )
+        }))
+    }
+}
+
+fn summarize(
+    found: FoundPlayer,
+    profile: Option<&PlayerProfile>,
+    ranks: ReconciledRanks,
+    heroes: &[Hero],
+    platform: Platform,
+    mode: Mode,
+) -> PlayerSummary {
+    let empty = HashMap::new();
+    let table = profile
+        .and_then(|p| p.stats(platform, mode))
+        .unwrap_or(&empty);
+    let played = |role: Role| {
+        heroes
+            .iter()
+            .filter(move |hero| hero.role == role)
+            .filter_map(|hero| Some((hero, table.get(&hero.name)?)))
+    };
+
+    let roles = [Role::Tank, Role::Damage, Role::Support]
+        .into_iter()
+        .map(|role| RoleStats {
+            role,
+            stats: play_stats(played(role).map(|(_, stats)| stats)),
+        })
+        .collect();
+
+    let mut top_heroes: Vec<_> = [Role::Tank, Role::Damage, Role::Support]
+        .into_iter()
+        .flat_map(played)
+        .map(|(hero, stats)| TopHero {
+            hero: hero.name.clone(),
+            role: hero.role,
+            time_played: play_stats([stats]).time_played,
+            stats: HEADLINE_STATS
+                .iter()
+                .filter_map(|name| Some(((*name).to_owned(), *stats.stats.get(*name)?)))
+                .collect(),
+        })
+        .collect();
+    top_heroes.sort_by_key(|hero| std::cmp::Reverse(hero.time_played));
+    top_heroes.truncate(TOP_HEROES);
+
+    let stats = table
+        .iter()
+        .find(|(hero, _)| hero.eq_ignore_ascii_case("all heroes"))
+        .map(|(_, stats)| play_stats([stats]));
+
+    let visibility = match profile {
+        Some(profile) => profile.visibility,
+        None if found.is_public => ProfileVisibility::Public,
+        None => ProfileVisibility::Private,
+    };
+
+    PlayerSummary {
+        title: found
+            .title
+            .as_ref()
+            .and_then(|t| t.get("en_US").cloned())
+            .or_else(|| profile.and_then(|p| p.title.clone())),
+        portrait: found
+            .portrait
+            .or_else(|| profile.map(|p| p.portrait.clone())),
+        endorsement: profile.and_then(|p| p.endorsement),
+        visibility,
+        private: visibility.is_private(),
+        last_updated: profile.map_or(found.last_updated, |p| p.last_updated),
+        battletag: found.battle_tag,
+        namecard: found.namecard,
+        ranks: ranks.ranks,
+        rank_conflicts: ranks.conflicts,
+        platform,
+        mode,
+        stats,
+        roles,
+        top_heroes,
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn play_stats<'a>(heroes: impl IntoIterator<Item = &'a HeroStats>) -> PlayStats {
+    let sum = |stats: &HeroStats, name| match stats.stats.get(name) {
+        Some(Stat::Duration(d)) => d.as_secs(),
+        Some(Stat::Number(n)) => *n as u64,
+        _ => 0,
+    };
+    let mut total = PlayStats::default();
+    for stats in heroes {
+        total.time_played += sum(stats, "Time Played");
+        total.wins += sum(stats, "Games Won");
+        total.ties += sum(stats, "Games Tied");
+        total.losses += sum(stats, "Games Lost");
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    use futures::{future::BoxFuture, FutureExt};
+    use sombra_types::SourceData;
+
+    use super::*;
+    use crate::{
+        parse_profile, CacheKey, CareerPageSource, Client, DataSource, Lifespans, LruStore,
+        OverbuffSource, Selectors, Upstream,
+    };
+
+    /// Stands in for Overbuff, counting how often it is asked.
+    struct Counting(Arc<AtomicU32>);
+
+    impl DataSource for Counting {
+        fn name(&self) -> &str {
+            OverbuffSource::NAME
+        }
+
+        fn fetch<'a>(
+            &'a self,
+            _: &'a Client,
+            _: &'a Battletag,
+        ) -> BoxFuture<'a, crate::Result<SourceData>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            let data = SourceData {
+                ranks: Vec::new(),
+                heroes: Vec::new(),
+                last_updated: None,
+            };
+            async move { Ok(data) }.boxed()
+        }
+    }
+
+    /// A client that has the search result and full profile parsed from `html` cached.
+    fn client(btag: &Battletag, html: &str) -> (CachedClient, Arc<AtomicU32>) {
+        let profile = parse_profile(btag, html, ProfileParts::ALL, &Selectors::default()).unwrap();
+        let found = FoundPlayer {
+            battle_tag: btag.clone(),
+            last_updated: profile.last_updated,
+            is_public: !profile.visibility.is_private(),
+            namecard: None,
+            portrait: None,
+            title: None,
+        };
+        let store = LruStore::new(Lifespans::new(60, 60, 60), 16);
+        store.set(CacheKey::search(&btag.name), Arc::new(vec![found]));
+        store.set(
+            CacheKey::profile(btag, ProfileParts::ALL),
+            Arc::new(profile),
+        );
+        let client = CachedClient::with_client(Client::unfetched(Upstream::default()), store);
+        let fetches = Arc::new(AtomicU32::new(0));
+        client.register_source(Counting(fetches.clone()), OverbuffSource::PRIORITY);
+        (client, fetches)
+    }
+
+    #[tokio::test]
+    async fn public_profiles_with_ranks_need_no_fallback() {
+        let btag = Battletag::new("Player", 1234);
+        let (client, fetches) = client(&btag, include_str!("../fixtures/career/public.html"));
+        let summary = client
+            .summary(&btag, Platform::Pc, Mode::Competitive)
+            .await
+            .unwrap()
+            .value;
+        assert_eq!(fetches.load(Ordering::SeqCst), 0);
+        assert!(!summary.ranks.is_empty());
+        assert!(summary
+            .ranks
+            .iter()
+            .all(|rank| rank.source == CareerPageSource::NAME));
+    }
+
+    #[tokio::test]
+    async fn private_profiles_fall_back_to_other_sources() {
+        let btag = Battletag::new("Private", 1234);
+        let (client, fetches) = client(&btag, include_str!("../fixtures/career/private.html"));
+        let summary = client
+            .summary(&btag, Platform::Pc, Mode::Competitive)
+            .await
+            .unwrap()
+            .value;
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert!(summary.private);
+    }
+}
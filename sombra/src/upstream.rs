@@ -0,0 +1,65 @@
+use sombra_types::Battletag;
+
+use crate::Client;
+
+pub const BLIZZARD_URL: &str = "https://overwatch.blizzard.com/en-us";
+pub const OVERBUFF_URL: &str = "https://www.overbuff.com";
+
+/// Base URLs of the sites that are scraped, without a trailing slash. They can be pointed at a
+/// mock server to run without the internet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Upstream {
+    pub blizzard: String,
+    pub overbuff: String,
+}
+
+impl Default for Upstream {
+    fn default() -> Self {
+        Self {
+            blizzard: BLIZZARD_URL.to_owned(),
+            overbuff: OVERBUFF_URL.to_owned(),
+        }
+    }
+}
+
+impl Upstream {
+    /// Uses `base` for every site, with the host of the real site as the first path segment,
+    /// e.g. `http://localhost:8001/www.overbuff.com`.
+    #[must_use]
+    pub fn mirror(base: &str) -> Self {
+        let base = base.trim_end_matches('/');
+        let mirror = |url: &str| format!("{base}/{}", url.trim_start_matches("https://"));
+        Self {
+            blizzard: mirror(BLIZZARD_URL),
+            overbuff: mirror(OVERBUFF_URL),
+        }
+    }
+
+    /// The search page that asset definitions are read from.
+    pub(crate) fn assets_url(&self) -> String {
+        format!("{}/search/", self.blizzard)
+    }
+
+    pub(crate) fn search_url(&self, name: &str) -> String {
+        format!("{}/search/account-by-name/{name}", self.blizzard)
+    }
+
+    pub(crate) fn career_url(&self, btag: &Battletag) -> String {
+        format!("{}/career/{btag:#}/", self.blizzard)
+    }
+
+    pub(crate) fn heroes_url(&self) -> String {
+        format!("{}/heroes/", self.blizzard)
+    }
+
+    pub(crate) fn overbuff_url(&self, btag: &Battletag) -> String {
+        format!("{}/players/{btag:#}/", self.overbuff)
+    }
+}
+
+impl Client {
+    #[must_use]
+    pub const fn upstream(&self) -> &Upstream {
+        &self.upstream
+    }
+}
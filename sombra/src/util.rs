@@ -49,14 +49,6 @@ pub fn find_inner_text<'dom>(dom: &'dom VDom<'dom>, selector: &'dom str) -> Opti
     Some(find(dom, selector)?.inner_text(dom.parser()).into())
 }
 
-pub fn find_inner_text2<'dom>(
-    dom: &'dom VDom<'dom>,
-    tag: &'dom HTMLTag<'dom>,
-    selector: &'dom str,
-) -> Option<String> {
-    Some(find2(dom, tag, selector)?.inner_text(dom.parser()).into())
-}
-
 pub fn find_attr<'dom>(dom: &'dom VDom<'dom>, selector: &'dom str, attr: &str) -> Option<String> {
     Some(
         find(dom, selector)?